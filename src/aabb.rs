@@ -0,0 +1,142 @@
+use crate::ray::Ray;
+use crate::vec3::{Point, Vec3};
+
+/* Axis-aligned bounding box */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    minimum: Point,
+    maximum: Point,
+}
+
+impl Aabb {
+    pub fn new(a: Point, b: Point) -> Aabb {
+        Aabb {
+            minimum: Vec3::min(&a, &b),
+            maximum: Vec3::max(&a, &b),
+        }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb {
+            minimum: Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            maximum: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb {
+            minimum: Vec3::min(&a.minimum, &b.minimum),
+            maximum: Vec3::max(&a.maximum, &b.maximum),
+        }
+    }
+
+    pub fn min(&self) -> &Point {
+        &self.minimum
+    }
+
+    pub fn max(&self) -> &Point {
+        &self.maximum
+    }
+
+    pub fn grow(&mut self, p: &Point) {
+        self.minimum = Vec3::min(&self.minimum, p);
+        self.maximum = Vec3::max(&self.maximum, p);
+    }
+
+    /* Widens any side thinner than `delta` to that size, so that flat
+     * objects get a box with some volume.
+     */
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut minimum = [0.0; 3];
+        let mut maximum = [0.0; 3];
+
+        for axis in 0..3 {
+            minimum[axis] = self.minimum[axis];
+            maximum[axis] = self.maximum[axis];
+            if maximum[axis] - minimum[axis] < delta {
+                let center = 0.5 * (minimum[axis] + maximum[axis]);
+                minimum[axis] = center - 0.5 * delta;
+                maximum[axis] = center + 0.5 * delta;
+            }
+        }
+
+        Aabb {
+            minimum: Point::new(minimum[0], minimum[1], minimum[2]),
+            maximum: Point::new(maximum[0], maximum[1], maximum[2]),
+        }
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        if d.x() < 0.0 || d.y() < 0.0 || d.z() < 0.0 {
+            return 0.0;
+        }
+
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /* The axis along which the box is longest */
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x() >= d.y() && d.x() >= d.z() {
+            0
+        } else if d.y() >= d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, t: (f64, f64)) -> bool {
+        let d = r.get_direction();
+        let inverse_direction = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+
+        self.intersect(r.get_origin(), &inverse_direction, t)
+            .is_some()
+    }
+
+    /* Slab test returning the distance at which the ray enters the box. The
+     * inverse direction is passed in so it can be computed once per ray when
+     * testing many boxes.
+     */
+    pub fn intersect(
+        &self,
+        origin: &Point,
+        inverse_direction: &Vec3,
+        t: (f64, f64),
+    ) -> Option<f64> {
+        self.clip(origin, inverse_direction, t)
+            .map(|(t_min, _)| t_min)
+    }
+
+    /* The part of the ray parameter interval `t` inside the box */
+    pub fn clip(
+        &self,
+        origin: &Point,
+        inverse_direction: &Vec3,
+        t: (f64, f64),
+    ) -> Option<(f64, f64)> {
+        let (mut t_min, mut t_max) = t;
+
+        for axis in 0..3 {
+            let mut t0 = (self.minimum[axis] - origin[axis]) * inverse_direction[axis];
+            let mut t1 = (self.maximum[axis] - origin[axis]) * inverse_direction[axis];
+            if inverse_direction[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // written so that a NaN from 0 * inf leaves the interval unchanged
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some((t_min, t_max))
+    }
+}
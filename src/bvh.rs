@@ -0,0 +1,345 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::objects::{HitRecord, Hittable, HittableList};
+use crate::ray::Ray;
+use crate::vec3::{Point, Vec3};
+
+const BINS: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+const STACK_SIZE: usize = 64;
+
+/* Nodes are stored depth first in one array. An interior node's left child
+ * directly follows it and `offset` is the index of its right child; a leaf
+ * covers `count` primitives starting at `offset`.
+ */
+struct Node {
+    bounds: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+struct Primitive {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point,
+}
+
+#[derive(Copy, Clone)]
+struct Bin {
+    bounds: Aabb,
+    count: usize,
+}
+
+/* Bounding volume hierarchy over the objects of a HittableList, built with
+ * the binned surface area heuristic. Objects without a bounding box are
+ * kept aside and tested against every ray.
+ */
+pub struct Bvh {
+    nodes: Vec<Node>,
+    objects: Vec<Arc<dyn Hittable>>,
+    unbounded: Vec<Arc<dyn Hittable>>,
+}
+
+impl Bvh {
+    pub fn new(list: HittableList) -> Bvh {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut primitives = Vec::new();
+
+        for obj in list.into_objects() {
+            match obj.bounding_box() {
+                Some(bounds) => {
+                    primitives.push(Primitive {
+                        index: bounded.len(),
+                        bounds,
+                        centroid: bounds.centroid(),
+                    });
+                    bounded.push(Some(obj));
+                }
+                None => unbounded.push(obj),
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(2 * primitives.len());
+        if !primitives.is_empty() {
+            build_node(&mut nodes, &mut primitives, 0);
+        }
+
+        // Reorder the objects so every leaf refers to a contiguous range.
+        let objects = primitives
+            .iter()
+            .map(|p| bounded[p.index].take().unwrap())
+            .collect();
+
+        Bvh {
+            nodes,
+            objects,
+            unbounded,
+        }
+    }
+}
+
+fn build_node(nodes: &mut Vec<Node>, primitives: &mut [Primitive], offset: usize) {
+    let mut bounds = Aabb::empty();
+    let mut centroid_bounds = Aabb::empty();
+    for p in primitives.iter() {
+        bounds = Aabb::surrounding(&bounds, &p.bounds);
+        centroid_bounds.grow(&p.centroid);
+    }
+
+    let index = nodes.len();
+    nodes.push(Node {
+        bounds,
+        offset,
+        count: primitives.len(),
+        axis: 0,
+    });
+
+    if primitives.len() == 1 {
+        return;
+    }
+
+    let axis = centroid_bounds.longest_axis();
+    let low = centroid_bounds.min()[axis];
+    let high = centroid_bounds.max()[axis];
+    let leaf_cost = INTERSECTION_COST * primitives.len() as f64;
+
+    let mid = if high - low <= 0.0 {
+        // All centroids coincide; no split can separate them.
+        if primitives.len() <= MAX_LEAF_SIZE {
+            return;
+        }
+        primitives.len() / 2
+    } else {
+        match best_split(primitives, &bounds, axis, low, high) {
+            Some((cost, split)) if cost < leaf_cost || primitives.len() > MAX_LEAF_SIZE => {
+                partition(primitives, |p| {
+                    bin_index(p.centroid[axis], low, high) < split
+                })
+            }
+            Some(_) => return,
+            None => {
+                if primitives.len() <= MAX_LEAF_SIZE {
+                    return;
+                }
+                primitives.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+                primitives.len() / 2
+            }
+        }
+    };
+
+    let (left, right) = primitives.split_at_mut(mid);
+    build_node(nodes, left, offset);
+    let right_index = nodes.len();
+    build_node(nodes, right, offset + mid);
+
+    let node = &mut nodes[index];
+    node.offset = right_index;
+    node.count = 0;
+    node.axis = axis;
+}
+
+fn bin_index(centroid: f64, low: f64, high: f64) -> usize {
+    let b = (BINS as f64 * (centroid - low) / (high - low)) as usize;
+    b.min(BINS - 1)
+}
+
+/* Evaluates the SAH cost of splitting after every bin boundary along `axis`
+ * and returns the cheapest as (cost, number of bins on the left). Only
+ * splits leaving primitives on both sides are considered.
+ */
+fn best_split(
+    primitives: &[Primitive],
+    bounds: &Aabb,
+    axis: usize,
+    low: f64,
+    high: f64,
+) -> Option<(f64, usize)> {
+    let mut bins = [Bin {
+        bounds: Aabb::empty(),
+        count: 0,
+    }; BINS];
+
+    for p in primitives {
+        let bin = &mut bins[bin_index(p.centroid[axis], low, high)];
+        bin.bounds = Aabb::surrounding(&bin.bounds, &p.bounds);
+        bin.count += 1;
+    }
+
+    // Sweep from the right to collect the area and count of every suffix.
+    let mut right_area = [0.0; BINS];
+    let mut right_count = [0; BINS];
+    let mut accumulated = Aabb::empty();
+    let mut count = 0;
+    for i in (1..BINS).rev() {
+        accumulated = Aabb::surrounding(&accumulated, &bins[i].bounds);
+        count += bins[i].count;
+        right_area[i] = accumulated.surface_area();
+        right_count[i] = count;
+    }
+
+    let parent_area = bounds.surface_area();
+    let mut best = None;
+    let mut accumulated = Aabb::empty();
+    let mut count = 0;
+    for split in 1..BINS {
+        accumulated = Aabb::surrounding(&accumulated, &bins[split - 1].bounds);
+        count += bins[split - 1].count;
+        if count == 0 || right_count[split] == 0 {
+            continue;
+        }
+
+        let cost = TRAVERSAL_COST
+            + INTERSECTION_COST
+                * (count as f64 * accumulated.surface_area()
+                    + right_count[split] as f64 * right_area[split])
+                / parent_area;
+
+        match best {
+            Some((best_cost, _)) if best_cost <= cost => {}
+            _ => best = Some((cost, split)),
+        }
+    }
+
+    best
+}
+
+/* Moves the primitives matching `left` to the front and returns how many
+ * there are.
+ */
+fn partition<F: Fn(&Primitive) -> bool>(primitives: &mut [Primitive], left: F) -> usize {
+    let mut mid = 0;
+    for i in 0..primitives.len() {
+        if left(&primitives[i]) {
+            primitives.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    mid
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let mut closest = t.1;
+        let mut record: Option<HitRecord> = None;
+
+        for obj in &self.unbounded {
+            if let (true, Some(rec)) = obj.hit(r, (t.0, closest)) {
+                closest = rec.t;
+                record = Some(rec);
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return (record.is_some(), record);
+        }
+
+        let origin = r.get_origin();
+        let d = r.get_direction();
+        let inverse_direction = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+        let negative = [d.x() < 0.0, d.y() < 0.0, d.z() < 0.0];
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut top = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node
+                .bounds
+                .intersect(origin, &inverse_direction, (t.0, closest))
+                .is_some()
+            {
+                if node.count > 0 {
+                    for obj in &self.objects[node.offset..node.offset + node.count] {
+                        if let (true, Some(rec)) = obj.hit(r, (t.0, closest)) {
+                            closest = rec.t;
+                            record = Some(rec);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer along the split axis first so
+                    // that hits there can cull the other one.
+                    let (near, far) = if negative[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+
+                    if top < STACK_SIZE {
+                        stack[top] = far;
+                        top += 1;
+                        current = near;
+                        continue;
+                    }
+
+                    // Deeper than the stack allows; fall back to recursion.
+                    let (hit, rec) = self.hit_subtree(far, r, (t.0, closest));
+                    if hit {
+                        closest = rec.as_ref().unwrap().t;
+                        record = rec;
+                    }
+                    current = near;
+                    continue;
+                }
+            }
+
+            if top == 0 {
+                break;
+            }
+            top -= 1;
+            current = stack[top];
+        }
+
+        (record.is_some(), record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        self.nodes.first().map(|node| node.bounds)
+    }
+}
+
+impl Bvh {
+    fn hit_subtree(&self, index: usize, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let node = &self.nodes[index];
+        if !node.bounds.hit(r, t) {
+            return (false, None);
+        }
+
+        if node.count > 0 {
+            let mut closest = t.1;
+            let mut record = None;
+            for obj in &self.objects[node.offset..node.offset + node.count] {
+                if let (true, Some(rec)) = obj.hit(r, (t.0, closest)) {
+                    closest = rec.t;
+                    record = Some(rec);
+                }
+            }
+            return (record.is_some(), record);
+        }
+
+        let (left_hit, left) = self.hit_subtree(index + 1, r, t);
+        let closest = if left_hit {
+            left.as_ref().unwrap().t
+        } else {
+            t.1
+        };
+        let (right_hit, right) = self.hit_subtree(node.offset, r, (t.0, closest));
+
+        if right_hit {
+            (true, right)
+        } else {
+            (left_hit, left)
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::ray::Ray;
+use crate::sampling;
 use crate::utility;
 use crate::vec3::{Point, Vec3};
 
@@ -7,10 +8,11 @@ pub struct Camera {
     lower_left_corner: Point,
     horizontal: Vec3,
     vertical: Vec3,
-    w: Vec3,
     u: Vec3,
     v: Vec3,
     lens_radius: f64,
+    /* Shutter open and close times */
+    shutter: (f64, f64),
 }
 
 impl Camera {
@@ -43,20 +45,31 @@ impl Camera {
             lower_left_corner,
             horizontal,
             vertical,
-            w,
             u,
             v,
             lens_radius: aperture / 2.0,
+            shutter: (0.0, 0.0),
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
-        let offset = self.u * rd.x() + self.v * rd.y();
+    /* Spreads rays over the times from `shutter.0` to `shutter.1` */
+    pub fn with_shutter(self, shutter: (f64, f64)) -> Camera {
+        Camera { shutter, ..self }
+    }
+
+    /* The ray through the viewport point (`s`, `t`), leaving the point of
+     * the lens and at the time within the shutter interval that the
+     * samples `lens` in [0, 1)^2 and `time` in [0, 1) map to.
+     */
+    pub fn get_ray(&self, s: f64, t: f64, lens: (f64, f64), time: f64) -> Ray {
+        let (x, y) = sampling::concentric_disk(lens);
+        let offset = self.lens_radius * (x * self.u + y * self.v);
+        let time = self.shutter.0 + time * (self.shutter.1 - self.shutter.0);
 
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
         )
     }
 }
@@ -0,0 +1,320 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::output::Format;
+use crate::sampler::SamplerKind;
+use crate::tonemap::{Operator, ToneMapping};
+
+pub const USAGE: &str = "\
+Usage: raytrace [OPTIONS] [SCENE]
+
+Renders SCENE, either the name of a built-in scene or the path to a scene
+file, and writes the image to a file or stdout. Settings given on the
+command line take precedence over those in the scene.
+
+Built-in scenes:
+    cover                   The random spheres cover image (default)
+    bouncing                The cover image with bouncing, motion blurred
+                            spheres
+    cornell                 The Cornell box, lit by a ceiling light
+    cornell-smoke           The Cornell box with blocks of smoke and fog
+
+Options:
+    -s, --scene <SCENE>     Scene to render, same as the positional argument
+    -o, --output <PATH>     Write the image to PATH instead of stdout
+    -f, --format <FORMAT>   Image format: png, ppm (binary), ppm-ascii, or
+                            the floating point hdr (Radiance), pfm and exr;
+                            by default taken from the output file's
+                            extension, ppm-ascii on stdout
+        --bit-depth <BITS>  Bits per channel of png and ppm images, 8 or 16
+                            (default: 8)
+        --tonemap <OPERATOR>
+                            How png and ppm images map radiance to display
+                            values: clamp (default), reinhard,
+                            reinhard-extended, aces or hable; floating point
+                            formats are always written untouched
+        --exposure <STOPS>  Exposure adjustment applied before tone mapping
+        --white <RADIANCE>  Smallest radiance mapped to white by
+                            reinhard-extended (default: 4)
+    -w, --width <PIXELS>    Image width
+    -h, --height <PIXELS>   Image height; derived from the width and the
+                            scene's aspect ratio when only one is given
+    -n, --samples <N>       Samples per pixel
+    -d, --max-depth <N>     Maximum number of bounces per path
+        --roulette-depth <N>
+                            Bounces after which paths may be ended early by
+                            Russian roulette (default: 3)
+        --sampler <SAMPLER> Where sample values come from: independent,
+                            stratified, halton, sobol (default) or
+                            blue-noise; all but independent random numbers
+                            spread the samples of a pixel evenly and
+                            converge faster
+        --shutter <OPEN>,<CLOSE>
+                            Scene time interval the shutter is open for;
+                            objects move between times 0 and 1, so frames
+                            of an animation use consecutive intervals such
+                            as 0,0.25 and 0.25,0.5
+        --seed <N>          Seed for the random numbers of the render and
+                            of random scene layouts (default: random); a
+                            fixed seed gives the same image for any thread
+                            count or tile size
+    -t, --threads <N>       Number of render threads (default: all cores)
+        --tile-size <PIXELS>
+                            Edge length of the square tiles the image is
+                            split into (default: 32)
+    -q, --quiet             Only print errors
+    -v, --verbose           Print render settings and timing
+        --help              Print this message
+";
+
+/* The most pixels an image may have: 16384 x 16384, whose framebuffer
+ * already takes 6 GiB.
+ */
+pub const MAX_PIXELS: usize = 1 << 28;
+
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+pub struct Options {
+    pub scene: String,
+    pub output: Option<PathBuf>,
+    pub format: Option<Format>,
+    pub bit_depth: u8,
+    pub tone_mapping: ToneMapping,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<i16>,
+    pub roulette_depth: Option<i16>,
+    pub sampler: Option<SamplerKind>,
+    pub shutter: Option<(f64, f64)>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub tile_size: usize,
+    pub verbosity: Verbosity,
+}
+
+impl Options {
+    /* Resolves the final image size against the scene's own settings. A
+     * single overridden dimension keeps the scene's aspect ratio. Sizes
+     * beyond `MAX_PIXELS` are refused before anything is allocated for them.
+     */
+    pub fn dimensions(
+        &self,
+        scene_width: usize,
+        scene_height: usize,
+    ) -> Result<(usize, usize), CliError> {
+        let aspect_ratio = scene_width as f64 / scene_height as f64;
+
+        let (width, height) = match (self.width, self.height) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, ((w as f64 / aspect_ratio) as usize).max(1)),
+            (None, Some(h)) => (((h as f64 * aspect_ratio) as usize).max(1), h),
+            (None, None) => (scene_width, scene_height),
+        };
+
+        // the widest rows written are 16 bit RGBA behind a PNG filter byte
+        let pixels = width.checked_mul(height);
+        let rows = width
+            .checked_mul(8)
+            .and_then(|row| (row + 1).checked_mul(height));
+        match (pixels, rows) {
+            (Some(pixels), Some(_)) if pixels <= MAX_PIXELS => Ok((width, height)),
+            _ => Err(CliError(format!(
+                "-w/-h: image too large; {}x{} is over {} pixels",
+                width, height, MAX_PIXELS
+            ))),
+        }
+    }
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Command, CliError> {
+    let mut options = Options {
+        scene: String::from("cover"),
+        output: None,
+        format: None,
+        bit_depth: 8,
+        tone_mapping: ToneMapping::default(),
+        width: None,
+        height: None,
+        samples_per_pixel: None,
+        max_depth: None,
+        roulette_depth: None,
+        sampler: None,
+        shutter: None,
+        seed: None,
+        threads: None,
+        tile_size: 32,
+        verbosity: Verbosity::Normal,
+    };
+    let mut scene = None;
+    let mut operator = String::from("clamp");
+    let mut white = 4.0;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => return Ok(Command::Help),
+            "-s" | "--scene" => set_scene(&mut scene, value(&arg, &mut args)?)?,
+            "-o" | "--output" => options.output = Some(PathBuf::from(value(&arg, &mut args)?)),
+            "-f" | "--format" => {
+                let v = value(&arg, &mut args)?;
+                match Format::from_name(&v) {
+                    Some(format) => options.format = Some(format),
+                    None => return Err(CliError(format!("{}: unknown format `{}`", arg, v))),
+                }
+            }
+            "--bit-depth" => {
+                let v = value(&arg, &mut args)?;
+                match v.as_str() {
+                    "8" => options.bit_depth = 8,
+                    "16" => options.bit_depth = 16,
+                    _ => {
+                        return Err(CliError(format!(
+                            "{}: expected 8 or 16, found `{}`",
+                            arg, v
+                        )))
+                    }
+                }
+            }
+            "--tonemap" => operator = value(&arg, &mut args)?,
+            "--exposure" => options.tone_mapping.exposure = number(&arg, &mut args)?,
+            "--white" => {
+                white = number(&arg, &mut args)?;
+                if white <= 0.0 {
+                    return Err(CliError(format!("{}: must be positive", arg)));
+                }
+            }
+            "-w" | "--width" => options.width = Some(positive(&arg, &mut args)?),
+            "-h" | "--height" => options.height = Some(positive(&arg, &mut args)?),
+            "-n" | "--samples" => {
+                let samples = positive(&arg, &mut args)?;
+                if samples > u32::MAX as usize {
+                    return Err(CliError(format!("{}: too many samples", arg)));
+                }
+                options.samples_per_pixel = Some(samples as u32);
+            }
+            "-d" | "--max-depth" => {
+                let depth = positive(&arg, &mut args)?;
+                if depth > i16::MAX as usize {
+                    return Err(CliError(format!("{}: must be at most {}", arg, i16::MAX)));
+                }
+                options.max_depth = Some(depth as i16);
+            }
+            "--roulette-depth" => {
+                let depth = positive(&arg, &mut args)?;
+                if depth > i16::MAX as usize {
+                    return Err(CliError(format!("{}: must be at most {}", arg, i16::MAX)));
+                }
+                options.roulette_depth = Some(depth as i16);
+            }
+            "--sampler" => {
+                let v = value(&arg, &mut args)?;
+                match SamplerKind::from_name(&v) {
+                    Some(sampler) => options.sampler = Some(sampler),
+                    None => return Err(CliError(format!("{}: unknown sampler `{}`", arg, v))),
+                }
+            }
+            "--shutter" => {
+                let v = value(&arg, &mut args)?;
+                let times: Vec<Option<f64>> = v
+                    .split(',')
+                    .map(|t| t.trim().parse::<f64>().ok().filter(|t| t.is_finite()))
+                    .collect();
+                match times.as_slice() {
+                    [Some(open), Some(close)] if open <= close => {
+                        options.shutter = Some((*open, *close));
+                    }
+                    _ => {
+                        return Err(CliError(format!(
+                            "{}: expected OPEN,CLOSE with OPEN <= CLOSE, found `{}`",
+                            arg, v
+                        )))
+                    }
+                }
+            }
+            "--seed" => {
+                let v = value(&arg, &mut args)?;
+                match v.parse::<u64>() {
+                    Ok(seed) => options.seed = Some(seed),
+                    Err(_) => {
+                        return Err(CliError(format!(
+                            "{}: expected a non-negative integer, found `{}`",
+                            arg, v
+                        )))
+                    }
+                }
+            }
+            "-t" | "--threads" => options.threads = Some(positive(&arg, &mut args)?),
+            "--tile-size" => options.tile_size = positive(&arg, &mut args)?,
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(CliError(format!("unknown option `{}`", arg)));
+            }
+            _ => set_scene(&mut scene, arg)?,
+        }
+    }
+
+    if let Some(s) = scene {
+        options.scene = s;
+    }
+
+    options.tone_mapping.operator = Operator::from_name(&operator, white)
+        .ok_or_else(|| CliError(format!("--tonemap: unknown operator `{}`", operator)))?;
+
+    Ok(Command::Render(options))
+}
+
+fn set_scene(scene: &mut Option<String>, value: String) -> Result<(), CliError> {
+    if scene.is_some() {
+        return Err(CliError(String::from("only one scene may be given")));
+    }
+
+    *scene = Some(value);
+    Ok(())
+}
+
+fn value<I: Iterator<Item = String>>(option: &str, args: &mut I) -> Result<String, CliError> {
+    args.next()
+        .ok_or_else(|| CliError(format!("{}: missing value", option)))
+}
+
+fn number<I: Iterator<Item = String>>(option: &str, args: &mut I) -> Result<f64, CliError> {
+    let v = value(option, args)?;
+    match v.parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(CliError(format!(
+            "{}: expected a number, found `{}`",
+            option, v
+        ))),
+    }
+}
+
+fn positive<I: Iterator<Item = String>>(option: &str, args: &mut I) -> Result<usize, CliError> {
+    let v = value(option, args)?;
+    match v.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(CliError(format!(
+            "{}: expected a positive integer, found `{}`",
+            option, v
+        ))),
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::objects::{HitRecord, Hittable, HittableList, Material};
+use crate::ray::Ray;
+use crate::rect::Quad;
+use crate::vec3::{Point, Vec3};
+
+/* An axis-aligned box made of six outward facing quads */
+pub struct Cuboid {
+    bounds: Aabb,
+    sides: HittableList,
+}
+
+impl Cuboid {
+    pub fn new(a: Point, b: Point, material: Arc<dyn Material>) -> Cuboid {
+        let bounds = Aabb::new(a, b);
+        let (min, max) = (*bounds.min(), *bounds.max());
+
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+        let faces = [
+            (Point::new(min.x(), min.y(), max.z()), dx, dy), // front
+            (Point::new(max.x(), min.y(), max.z()), -dz, dy), // right
+            (Point::new(max.x(), min.y(), min.z()), -dx, dy), // back
+            (Point::new(min.x(), min.y(), min.z()), dz, dy), // left
+            (Point::new(min.x(), max.y(), max.z()), dx, -dz), // top
+            (Point::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+        ];
+
+        let mut sides = HittableList::new();
+        for (corner, u, v) in faces.iter() {
+            sides.add(Arc::new(Quad::new(*corner, *u, *v, material.clone())));
+        }
+
+        Cuboid { bounds, sides }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        self.sides.hit(r, t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.padded(1e-4))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        self.sides.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        self.sides.random(origin, u)
+    }
+}
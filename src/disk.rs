@@ -0,0 +1,99 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::objects::{planar_pdf, HitRecord, Hittable, Material};
+use crate::ray::Ray;
+use crate::sampling::{self, Onb};
+use crate::vec3::{Point, Vec3};
+
+/* A flat disk facing along `normal`. u is the angle around the center and v
+ * the distance from it, both scaled to [0, 1].
+ */
+pub struct Disk {
+    center: Point,
+    normal: Vec3,
+    radius: f64,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Disk {
+        let normal = normal.unit();
+        let basis = Onb::new(&normal);
+
+        Disk {
+            center,
+            normal,
+            radius,
+            tangent: *basis.u(),
+            bitangent: *basis.v(),
+            material,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let denominator = Vec3::dot(&self.normal, r.get_direction());
+        if denominator.abs() < 1e-8 {
+            return (false, None);
+        }
+
+        let root = Vec3::dot(&self.normal, &(self.center - *r.get_origin())) / denominator;
+        if root < t.0 || root > t.1 {
+            return (false, None);
+        }
+
+        let point = r.at(root);
+        let offset = point - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return (false, None);
+        }
+
+        let angle = Vec3::dot(&offset, &self.bitangent).atan2(Vec3::dot(&offset, &self.tangent));
+        let uv = (
+            (angle + PI) / (2.0 * PI),
+            distance_squared.sqrt() / self.radius,
+        );
+
+        let mut rec = HitRecord::new(point, self.normal, root, uv, false, self.material.as_ref());
+        rec.set_face_normal(r, self.normal);
+
+        (true, Some(rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the disk's extent along an axis shrinks as the normal turns towards it
+        let n = self.normal;
+        let extent = self.radius
+            * Vec3::new(
+                (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+                (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+                (1.0 - n.z() * n.z()).max(0.0).sqrt(),
+            );
+
+        Some(Aabb::new(self.center - extent, self.center + extent).padded(1e-4))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        match self.hit(&ray, (0.001, f64::INFINITY)) {
+            (true, Some(rec)) => {
+                let area = PI * self.radius * self.radius;
+                planar_pdf(direction, rec.t, &self.normal, area)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        let (x, y) = sampling::concentric_disk(u);
+        let point = self.center + self.radius * (x * self.tangent + y * self.bitangent);
+
+        point - *origin
+    }
+}
@@ -0,0 +1,96 @@
+use std::io::{self, Write};
+
+use crate::vec3::Color;
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn box2i(width: usize, height: usize) -> Vec<u8> {
+    let mut value = Vec::with_capacity(16);
+    for v in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        value.extend_from_slice(&v.to_le_bytes());
+    }
+    value
+}
+
+/* Writes a single-part scanline OpenEXR file with uncompressed 32-bit float
+ * R, G and B channels from linear colors given row by row from the top.
+ */
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    // Channels are listed, and stored within each scanline, alphabetically.
+    let mut channels = Vec::new();
+    for name in &["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channels.push(0);
+
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "displayWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // One scanline per block, each prefixed by its y coordinate and size.
+    let line_size = width * 3 * 4;
+    let block_size = 8 + line_size;
+    let first_block = header.len() + 8 * height;
+
+    out.write_all(&header)?;
+    for y in 0..height {
+        out.write_all(&((first_block + y * block_size) as u64).to_le_bytes())?;
+    }
+
+    let mut block = Vec::with_capacity(block_size);
+    for (y, row) in pixels.chunks(width).enumerate() {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&(line_size as i32).to_le_bytes());
+
+        for &channel in &[2, 1, 0] {
+            for pixel in row {
+                block.extend_from_slice(&(pixel[channel] as f32).to_le_bytes());
+            }
+        }
+        out.write_all(&block)?;
+    }
+
+    Ok(())
+}
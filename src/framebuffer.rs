@@ -0,0 +1,53 @@
+use crate::tonemap::ToneMapping;
+use crate::vec3::Color;
+
+/* Linear pixel colors, row by row from the top of the image. */
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /* Tone maps, sRGB encodes and quantizes every channel to 0..=max_value,
+     * three samples per pixel.
+     */
+    pub fn display_samples(&self, max_value: u16, tone_mapping: &ToneMapping) -> Vec<u16> {
+        let levels = max_value as f64 + 1.0;
+        let quantize = |v: f64| (levels * v).min(max_value as f64) as u16;
+
+        let mut samples = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            let display = tone_mapping.display(*pixel);
+            samples.push(quantize(display.x()));
+            samples.push(quantize(display.y()));
+            samples.push(quantize(display.z()));
+        }
+
+        samples
+    }
+}
@@ -0,0 +1,261 @@
+use std::io::{self, Write};
+
+use crate::vec3::Color;
+
+/* Shared-exponent encoding of a color: the mantissas of the three channels
+ * relative to the largest one and a biased exponent.
+ */
+fn rgbe(color: &Color) -> [u8; 4] {
+    let r = color.x().max(0.0);
+    let g = color.y().max(0.0);
+    let b = color.z().max(0.0);
+    let max = r.max(g).max(b);
+
+    if !max.is_finite() || max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // max = m * 2^e with m in [0.5, 1)
+    let mut e = max.log2().floor() as i32 + 1;
+    if max / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2f64.powi(e);
+
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128).clamp(0, 255) as u8,
+    ]
+}
+
+/* Run-length encodes one component of a scanline: runs of four or more equal
+ * bytes are stored as (128 + length, value), everything else as a literal
+ * count followed by the bytes.
+ */
+fn write_rle<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    let run_at = |i: usize, max: usize| {
+        let mut run = 1;
+        while i + run < data.len() && run < max && data[i + run] == data[i] {
+            run += 1;
+        }
+        run
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i, 127);
+        if run >= 4 {
+            out.write_all(&[128 + run as u8, data[i]])?;
+            i += run;
+            continue;
+        }
+
+        let start = i;
+        while i < data.len() && i - start < 128 && run_at(i, 4) < 4 {
+            i += 1;
+        }
+        out.write_all(&[(i - start) as u8])?;
+        out.write_all(&data[start..i])?;
+    }
+
+    Ok(())
+}
+
+/* Writes a Radiance RGBE (.hdr) image from linear colors, row by row from the
+ * top.
+ */
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+
+    // Scanlines outside this range cannot be run-length encoded.
+    let encode = (8..32768).contains(&width);
+    let mut components: Vec<Vec<u8>> = (0..4).map(|_| Vec::with_capacity(width)).collect();
+
+    for row in pixels.chunks(width) {
+        if !encode {
+            for pixel in row {
+                out.write_all(&rgbe(pixel))?;
+            }
+            continue;
+        }
+
+        for c in components.iter_mut() {
+            c.clear();
+        }
+        for pixel in row {
+            for (c, value) in components.iter_mut().zip(rgbe(pixel).iter()) {
+                c.push(*value);
+            }
+        }
+
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for c in &components {
+            write_rle(out, c)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn from_rgbe(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    // the + 0.5 recenters the truncated mantissas
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Color::new(
+        (rgbe[0] as f64 + 0.5) * f,
+        (rgbe[1] as f64 + 0.5) * f,
+        (rgbe[2] as f64 + 0.5) * f,
+    )
+}
+
+fn read_byte(data: &[u8], position: &mut usize) -> io::Result<u8> {
+    let byte = *data
+        .get(*position)
+        .ok_or_else(|| invalid("truncated Radiance HDR data"))?;
+    *position += 1;
+    Ok(byte)
+}
+
+/* Decodes one run-length encoded scanline into RGBE quadruples. */
+fn read_rle_scanline(data: &[u8], position: &mut usize, scanline: &mut [u8]) -> io::Result<()> {
+    let width = scanline.len() / 4;
+
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = read_byte(data, position)? as usize;
+            if count > 128 {
+                let run = count - 128;
+                let value = read_byte(data, position)?;
+                if x + run > width {
+                    return Err(invalid("Radiance HDR run overflows its scanline"));
+                }
+                for _ in 0..run {
+                    scanline[4 * x + component] = value;
+                    x += 1;
+                }
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid("invalid Radiance HDR literal run"));
+                }
+                for _ in 0..count {
+                    scanline[4 * x + component] = read_byte(data, position)?;
+                    x += 1;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/* Decodes a Radiance RGBE image in the standard -Y +X orientation into
+ * (width, height, linear colors) row by row from the top.
+ */
+pub fn read(data: &[u8]) -> io::Result<(usize, usize, Vec<Color>)> {
+    if !data.starts_with(b"#?") {
+        return Err(invalid("not a Radiance HDR image"));
+    }
+
+    // The header is a list of lines ended by an empty one, followed by the
+    // resolution line.
+    let mut position = 0;
+    let next_line = |position: &mut usize| -> io::Result<String> {
+        let end = data[*position..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("truncated Radiance HDR header"))?;
+        let line = String::from_utf8_lossy(&data[*position..*position + end]).into_owned();
+        *position += end + 1;
+        Ok(line)
+    };
+
+    loop {
+        let line = next_line(&mut position)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid("unsupported Radiance HDR pixel format"));
+            }
+        }
+    }
+
+    let resolution = next_line(&mut position)?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match fields.as_slice() {
+        ["-Y", h, "+X", w] => match (h.parse::<usize>(), w.parse::<usize>()) {
+            (Ok(h), Ok(w)) if h > 0 && w > 0 => (h, w),
+            _ => return Err(invalid("invalid Radiance HDR resolution")),
+        },
+        _ => return Err(invalid("unsupported Radiance HDR orientation")),
+    };
+
+    // Every scanline takes at least four bytes per pixel or, run length
+    // encoded, a header and a two byte run of up to 127 pixels for each
+    // component, so a resolution the data cannot hold is rejected before
+    // allocating for it.
+    let encodable = (8..32768).contains(&width);
+    let scanline_bytes = if encodable {
+        4 + 8 * width.div_ceil(127)
+    } else {
+        width
+            .checked_mul(4)
+            .ok_or_else(|| invalid("Radiance HDR resolution too large"))?
+    };
+    let size = width
+        .checked_mul(height)
+        .filter(|_| {
+            scanline_bytes
+                .checked_mul(height)
+                .is_some_and(|bytes| bytes <= data.len() - position)
+        })
+        .ok_or_else(|| invalid("truncated Radiance HDR data"))?;
+
+    let mut pixels = Vec::with_capacity(size);
+    let mut scanline = vec![0u8; 4 * width];
+
+    for _ in 0..height {
+        let start = data
+            .get(position..position + 4)
+            .ok_or_else(|| invalid("truncated Radiance HDR data"))?;
+        let encoded = encodable
+            && start[0] == 2
+            && start[1] == 2
+            && ((start[2] as usize) << 8 | start[3] as usize) == width;
+
+        if encoded {
+            position += 4;
+            read_rle_scanline(data, &mut position, &mut scanline)?;
+        } else {
+            let flat = data
+                .get(position..position + 4 * width)
+                .ok_or_else(|| invalid("truncated Radiance HDR data"))?;
+            scanline.copy_from_slice(flat);
+            position += 4 * width;
+        }
+
+        pixels.extend(scanline.chunks(4).map(from_rgbe));
+    }
+
+    Ok((width, height, pixels))
+}
@@ -1,176 +1,184 @@
+mod aabb;
+mod bvh;
 mod camera;
+mod cli;
+mod cuboid;
+mod disk;
+mod exr;
+mod framebuffer;
+mod hdr;
+mod medium;
+mod mesh;
+mod noise;
+mod obj;
 mod objects;
+mod output;
+mod pfm;
+mod phase;
+mod ply;
+mod png;
+mod ppm;
 mod ray;
+mod rect;
+mod render;
+mod sampler;
+mod sampling;
+mod scene;
+mod scenes;
 mod sphere;
+mod texture;
+mod tonemap;
+mod transform;
 mod utility;
 mod vec3;
-
-use std::rc::Rc;
-use vec3::{Color, Point, Vec3};
-
-use rand;
-
-use camera::Camera;
-use objects::{HitRecord, HittableList};
-use ray::Ray;
-use sphere::Sphere;
-
-fn ray_color(r: &Ray, world: &HittableList, depth: i16) -> Color {
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    let (hit, record_opt) = world.hit(r, (0.001, std::f64::INFINITY));
-    if hit {
-        let record = record_opt.unwrap();
-        let target = record.point + Vec3::random_in_hemisphere(&record.normal);
-
-        let (scattered, attenuation, scattered_ray) = record.material.scatter(r, &record);
-        if scattered {
-            return attenuation * ray_color(&scattered_ray, world, depth - 1);
-        } else {
-            return Color::new(0.0, 0.0, 0.0);
-        }
+mod zlib;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+use bvh::Bvh;
+use cli::{Command, Verbosity};
+use output::Format;
+use render::{RenderSettings, World};
+use scene::{Scene, SceneError};
+
+fn load_scene(name: &str, seed: u64) -> Result<Scene, SceneError> {
+    match scenes::builtin(name, seed) {
+        Some(scene) => Ok(scene),
+        None => Scene::load(name),
     }
+}
 
-    let unit_direction = r.get_direction().unit();
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+fn fail(message: String) -> ! {
+    eprintln!("raytrace: {}", message);
+    process::exit(1);
 }
 
-fn hit_sphere(center: &Point, radius: f64, r: &Ray) -> f64 {
-    let oc = *r.get_origin() - *center;
+fn main() {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("raytrace: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-    let a = r.get_direction().length_squared();
-    let half_b = Vec3::dot(&oc, r.get_direction());
-    let c = oc.length_squared() - radius * radius;
-    let discriminant = half_b * half_b - a * c;
+    // The scene is built with the same seed so random layouts repeat too.
+    let seed = options.seed.unwrap_or_else(rand::random::<u64>);
 
-    if discriminant < 0.0 {
-        return -1.0;
-    } else {
-        return (-half_b - discriminant.sqrt()) / a;
-    }
-}
+    let mut scene = load_scene(&options.scene, seed)
+        .unwrap_or_else(|e| fail(format!("{}: {}", options.scene, e)));
 
-fn main() {
     // image
-    let aspect_ratio = 3.0 / 2.0;
-    let image_width = 1200;
-    let image_height = (image_width as f64 / aspect_ratio) as i64;
-    let samples_per_pixel = 500;
-    let max_bounces = 50;
+    let (image_width, image_height) = options
+        .dimensions(scene.image.width, scene.image.height)
+        .unwrap_or_else(|e| fail(e.to_string()));
+    let aspect_ratio = image_width as f64 / image_height as f64;
+    let settings = RenderSettings {
+        width: image_width,
+        height: image_height,
+        samples_per_pixel: options
+            .samples_per_pixel
+            .unwrap_or(scene.image.samples_per_pixel),
+        max_depth: options.max_depth.unwrap_or(scene.image.max_depth),
+        roulette_depth: options.roulette_depth.unwrap_or(scene.image.roulette_depth),
+        sampler: options.sampler.unwrap_or(scene.image.sampler),
+        seed,
+        threads: options.threads.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        }),
+        tile_size: options.tile_size,
+    };
 
     // camera
-    let look_from = Point::new(13.0, 2.0, 3.0);
-    let look_at = Point::new(0.0, 0.0, 0.0);
-    let distance_to_focus = 10.0;
-    let camera = Camera::new(
-        look_from,
-        look_at,
-        Vec3::new(0.0, 1.0, 0.0),
-        20.0,
-        aspect_ratio,
-        0.1,
-        distance_to_focus,
-    );
-
-    let mut world = HittableList::new();
-
-    let ground_material = Rc::new(objects::Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Rc::new(sphere::Sphere::new(
-        Point::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material.clone(),
-    )));
-
-    for a in (-11..11) {
-        for b in (-11..11) {
-            let mat = rand::random::<f64>();
-            let center = Point::new(
-                a as f64 + 0.9 * rand::random::<f64>(),
-                0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
-            );
-
-            if (center - Point::new(4.0, 0.2, 0.0)).length() <= 0.9 {
-                continue;
-            }
+    if let Some(shutter) = options.shutter {
+        scene.camera.shutter = shutter;
+    }
+    let camera = scene.camera.build(aspect_ratio);
+    let object_count = scene.world.len();
+    let bvh_start = Instant::now();
+    let objects = Bvh::new(scene.world);
+    if options.verbosity >= Verbosity::Verbose {
+        eprintln!(
+            "Built BVH over {} objects in {:.2?}",
+            object_count,
+            bvh_start.elapsed()
+        );
+    }
 
-            if mat < 0.8 {
-                let albedo = Color::random() * Color::random();
-                world.add(Rc::new(sphere::Sphere::new(
-                    center,
-                    0.2,
-                    Rc::new(objects::Lambertian::new(albedo)),
-                )));
-            } else if mat < 0.95 {
-                let albedo = Color::random_rng(0.5, 1.0);
-                let fuzz = utility::random(0.0, 0.5);
-                world.add(Rc::new(sphere::Sphere::new(
-                    center,
-                    0.2,
-                    Rc::new(objects::Metal::new(albedo, fuzz)),
-                )));
-            } else {
-                world.add(Rc::new(sphere::Sphere::new(
-                    center,
-                    0.2,
-                    Rc::new(objects::Dielectric::new(1.5)),
-                )));
+    let format = match (options.format, &options.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => Format::from_path(path).unwrap_or_else(|| {
+            fail(format!(
+                "{}: cannot tell the image format from the extension; use --format",
+                path.display()
+            ))
+        }),
+        (None, None) => Format::PpmAscii,
+    };
+
+    let output: Box<dyn Write> = match &options.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => fail(format!("{}: {}", path.display(), e)),
+        },
+        None => Box::new(io::stdout()),
+    };
+    let mut output = BufWriter::new(output);
+
+    if options.verbosity >= Verbosity::Verbose {
+        eprintln!(
+            "Rendering `{}` at {}x{}, {} samples per pixel, max depth {}, roulette after {}, {} sampler, seed {}, {} threads",
+            options.scene,
+            settings.width,
+            settings.height,
+            settings.samples_per_pixel,
+            settings.max_depth,
+            settings.roulette_depth,
+            settings.sampler.name(),
+            settings.seed,
+            settings.threads
+        );
+    }
+    let start = Instant::now();
+
+    let world = World {
+        objects: &objects,
+        media: &scene.media,
+        lights: &scene.lights,
+        background: scene.background,
+    };
+
+    let framebuffer = render::render(&camera, &world, &settings, |done, total| {
+        if options.verbosity >= Verbosity::Normal {
+            eprint!("\rTiles remaining: {:<8}", total - done);
+            if done == total {
+                eprintln!();
             }
         }
+    });
+
+    if let Err(e) = output::write(
+        &mut output,
+        &framebuffer,
+        format,
+        options.bit_depth,
+        &options.tone_mapping,
+    ) {
+        fail(format!("failed to write image: {}", e));
     }
 
-    let material1 = Rc::new(objects::Dielectric::new(1.5));
-    world.add(Rc::new(sphere::Sphere::new(
-        Point::new(0.0, 1.0, 0.0),
-        1.0,
-        material1.clone(),
-    )));
-
-    let material2 = Rc::new(objects::Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Rc::new(sphere::Sphere::new(
-        Point::new(-4.0, 1.0, 0.0),
-        1.0,
-        material2.clone(),
-    )));
-
-    let material3 = Rc::new(objects::Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(sphere::Sphere::new(
-        Point::new(4.0, 1.0, 0.0),
-        1.0,
-        material3.clone(),
-    )));
-
-    print!("P3\n{} {}\n255\n", image_width, image_height);
-
-    for j in (0..image_height).rev() {
-        eprintln!("Scanline {}", j);
-
-        for i in 0..image_width {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-
-            for s in 0..samples_per_pixel {
-                let u = (i as f64 + rand::random::<f64>()) / (image_width - 1) as f64;
-                let v = (j as f64 + rand::random::<f64>()) / (image_height - 1) as f64;
-                let ray = camera.get_ray(u, v);
-                pixel_color += ray_color(&ray, &world, max_bounces);
-            }
-
-            let scale = 1.0 / samples_per_pixel as f64;
-
-            // divide by scale and gamma correct
-            let r = (pixel_color.x() * scale).sqrt();
-            let g = (pixel_color.y() * scale).sqrt();
-            let b = (pixel_color.z() * scale).sqrt();
-
-            print!(
-                "{} {} {}\n",
-                (256.0 * utility::clamp(r, 0.0, 0.999)) as i64,
-                (256.0 * utility::clamp(g, 0.0, 0.999)) as i64,
-                (256.0 * utility::clamp(b, 0.0, 0.999)) as i64
-            );
-        }
+    if options.verbosity >= Verbosity::Verbose {
+        eprintln!("Done in {:.2?}", start.elapsed());
     }
 }
@@ -0,0 +1,388 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::noise::{Perlin, Smoothing};
+use crate::objects::{HitRecord, Hittable, Material};
+use crate::ray::Ray;
+use crate::utility::{self, Rng};
+use crate::vec3::{Point, Vec3};
+
+/* A volume of scattering particles, such as smoke, fog or clouds. Unlike
+ * the objects in the world media are not intersected but tracked along
+ * rays by the renderer, which asks for the next collision with `collide`
+ * and for how much light passes between two points with `transmittance`,
+ * both drawing their random numbers from the path's `rng`.
+ */
+pub trait Medium: Send + Sync {
+    /* Where along the ray, between `t.0` and `t.1`, it scatters, if it
+     * does, with the medium's phase function as the material.
+     */
+    fn collide(&self, r: &Ray, t: (f64, f64), rng: &mut Rng) -> Option<HitRecord<'_>>;
+
+    /* The fraction of light getting through the part of the ray between
+     * `t.0` and `t.1`.
+     */
+    fn transmittance(&self, r: &Ray, t: (f64, f64), rng: &mut Rng) -> f64;
+}
+
+/* A volume of uniform density filling a closed boundary, such as smoke or
+ * fog. Rays passing through scatter after an exponentially distributed
+ * distance, at which point `phase` decides the new direction.
+ */
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+        }
+    }
+
+    /* The part of the ray between `t.0` and `t.1` inside the boundary */
+    fn inside(&self, r: &Ray, t: (f64, f64)) -> Option<(f64, f64)> {
+        // Find where the ray's line enters and leaves the boundary, which
+        // also handles rays starting inside it. Only convex boundaries are
+        // handled exactly.
+        let enter = match self.boundary.hit(r, (f64::NEG_INFINITY, f64::INFINITY)) {
+            (true, Some(rec)) => rec.t,
+            _ => return None,
+        };
+        let exit = match self.boundary.hit(r, (enter + 0.0001, f64::INFINITY)) {
+            (true, Some(rec)) => rec.t,
+            _ => return None,
+        };
+
+        let enter = enter.max(t.0).max(0.0);
+        let exit = exit.min(t.1);
+        if enter >= exit {
+            return None;
+        }
+
+        Some((enter, exit))
+    }
+}
+
+impl Medium for ConstantMedium {
+    fn collide(&self, r: &Ray, t: (f64, f64), rng: &mut Rng) -> Option<HitRecord<'_>> {
+        let (enter, exit) = self.inside(r, t)?;
+
+        let ray_length = r.get_direction().length();
+        let distance_inside = (exit - enter) * ray_length;
+        let hit_distance = -(1.0 - rng.double()).ln() / self.density;
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = enter + hit_distance / ray_length;
+
+        // normal and facing mean nothing inside a volume
+        Some(HitRecord::new(
+            r.at(t),
+            Vec3::new(1.0, 0.0, 0.0),
+            t,
+            (0.0, 0.0),
+            true,
+            self.phase.as_ref(),
+        ))
+    }
+
+    /* Known exactly for a uniform density, so no random numbers are needed */
+    fn transmittance(&self, r: &Ray, t: (f64, f64), _rng: &mut Rng) -> f64 {
+        match self.inside(r, t) {
+            Some((enter, exit)) => {
+                (-self.density * (exit - enter) * r.get_direction().length()).exp()
+            }
+            None => 1.0,
+        }
+    }
+}
+
+/* Density varying through a region of space */
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: &Point) -> f64;
+
+    /* No point of the field is denser than this */
+    fn max_density(&self) -> f64;
+
+    /* The density is zero outside of this box */
+    fn bounds(&self) -> Aabb;
+}
+
+/* Densities sampled on a regular grid of voxels filling a box, trilinearly
+ * interpolated between voxel centers.
+ */
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    values: Vec<f64>,
+    bounds: Aabb,
+    max: f64,
+}
+
+impl VoxelGrid {
+    /* `values` run along x first, then y, then z */
+    pub fn new(resolution: [usize; 3], values: Vec<f64>, bounds: Aabb) -> io::Result<VoxelGrid> {
+        let count = resolution
+            .iter()
+            .try_fold(1, |count: usize, &n| count.checked_mul(n));
+        if count != Some(values.len()) || values.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "grid resolution does not match its voxel count",
+            ));
+        }
+        let max = values.iter().cloned().fold(0.0, f64::max);
+
+        Ok(VoxelGrid {
+            resolution,
+            values,
+            bounds,
+            max,
+        })
+    }
+
+    /* Reads a raw grid: nothing but the voxels' densities as little endian
+     * 32 bit floats, in the order `new` expects.
+     */
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        resolution: [usize; 3],
+        bounds: Aabb,
+    ) -> io::Result<VoxelGrid> {
+        let data = fs::read(path)?;
+        let size = format!("{}x{}x{}", resolution[0], resolution[1], resolution[2]);
+        let bytes = resolution
+            .iter()
+            .try_fold(4, |bytes: usize, &n| bytes.checked_mul(n))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("a {} grid is too large", size),
+                )
+            })?;
+        if bytes == 0 || data.len() != bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} bytes for a {} grid, found {}",
+                    bytes,
+                    size,
+                    data.len()
+                ),
+            ));
+        }
+
+        let values: Vec<f64> = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        if values.iter().any(|v| !(*v >= 0.0 && v.is_finite())) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "grid densities must be finite and not negative",
+            ));
+        }
+
+        VoxelGrid::new(resolution, values, bounds)
+    }
+
+    fn value(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.values[i + self.resolution[0] * (j + self.resolution[1] * k)]
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Point) -> f64 {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let mut cells = [(0, 0, 0.0); 3];
+
+        for axis in 0..3 {
+            if p[axis] < min[axis] || p[axis] > max[axis] {
+                return 0.0;
+            }
+
+            // voxel centers sit at half integer grid coordinates
+            let last = self.resolution[axis] - 1;
+            let x = (p[axis] - min[axis]) / (max[axis] - min[axis]) * self.resolution[axis] as f64
+                - 0.5;
+            let x = utility::clamp(x, 0.0, last as f64);
+            let i = x.floor() as usize;
+            cells[axis] = (i, (i + 1).min(last), x - i as f64);
+        }
+
+        let [(i0, i1, wx), (j0, j1, wy), (k0, k1, wz)] = cells;
+        let lerp = |a: f64, b: f64, w: f64| a + w * (b - a);
+        let near = lerp(
+            lerp(self.value(i0, j0, k0), self.value(i1, j0, k0), wx),
+            lerp(self.value(i0, j1, k0), self.value(i1, j1, k0), wx),
+            wy,
+        );
+        let far = lerp(
+            lerp(self.value(i0, j0, k1), self.value(i1, j0, k1), wx),
+            lerp(self.value(i0, j1, k1), self.value(i1, j1, k1), wx),
+            wy,
+        );
+
+        lerp(near, far, wz)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+/* Cloud-like puffs of fractal noise filling a box. `coverage` shifts the
+ * noise up or down, between -1 for nothing and 1 for a nearly solid block.
+ */
+pub struct NoiseField {
+    noise: Perlin,
+    frequency: f64,
+    octaves: u32,
+    coverage: f64,
+    bounds: Aabb,
+}
+
+impl NoiseField {
+    pub fn new(seed: u64, frequency: f64, octaves: u32, coverage: f64, bounds: Aabb) -> NoiseField {
+        NoiseField {
+            noise: Perlin::new(seed, Smoothing::Hermite),
+            frequency,
+            octaves,
+            coverage,
+            bounds,
+        }
+    }
+}
+
+impl DensityField for NoiseField {
+    fn density(&self, p: &Point) -> f64 {
+        let value = self.coverage + self.noise.fbm(&(self.frequency * *p), self.octaves);
+        utility::clamp(value, 0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        1.0
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+/* A volume whose density varies from point to point, scaled by `density` */
+pub struct HeterogeneousMedium {
+    field: Box<dyn DensityField>,
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        field: Box<dyn DensityField>,
+        density: f64,
+        phase: Arc<dyn Material>,
+    ) -> HeterogeneousMedium {
+        HeterogeneousMedium {
+            field,
+            density,
+            phase,
+        }
+    }
+}
+
+impl Medium for HeterogeneousMedium {
+    /* Ratio tracking: the fraction of light getting through the part of the
+     * ray between `t.0` and `t.1`. Tentative collisions are placed as in
+     * `collide`, but rather than stopping at a real one each multiplies the
+     * estimate by the chance it was a null collision, which gives a smooth
+     * estimate where delta tracking could only answer all or nothing.
+     */
+    fn transmittance(&self, r: &Ray, t: (f64, f64), rng: &mut Rng) -> f64 {
+        let majorant = self.density * self.field.max_density();
+        if majorant <= 0.0 {
+            return 1.0;
+        }
+
+        let d = r.get_direction();
+        let inverse_direction = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+        let (enter, exit) = match self
+            .field
+            .bounds()
+            .clip(r.get_origin(), &inverse_direction, t)
+        {
+            Some(interval) => interval,
+            None => return 1.0,
+        };
+
+        let step = 1.0 / (majorant * d.length());
+        let mut transmittance = 1.0;
+        let mut t = enter;
+        loop {
+            t -= step * (1.0 - rng.double()).ln();
+            if t >= exit {
+                return transmittance;
+            }
+
+            transmittance *= 1.0 - self.density * self.field.density(&r.at(t)) / majorant;
+        }
+    }
+
+    /* Delta tracking: tentative collisions are spaced as in a uniform medium
+     * as dense as the densest point, and each is real with the probability
+     * of the actual density over that bound. The rest are null collisions
+     * that leave the ray unchanged, which keeps the distance to the first
+     * real one distributed exactly as in the varying medium.
+     */
+    fn collide(&self, r: &Ray, t: (f64, f64), rng: &mut Rng) -> Option<HitRecord<'_>> {
+        let majorant = self.density * self.field.max_density();
+        if majorant <= 0.0 {
+            return None;
+        }
+
+        let d = r.get_direction();
+        let inverse_direction = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+        let (enter, exit) = self
+            .field
+            .bounds()
+            .clip(r.get_origin(), &inverse_direction, t)?;
+
+        // steps are drawn in distance and converted to the ray parameter
+        let step = 1.0 / (majorant * d.length());
+        let mut t = enter;
+        loop {
+            t -= step * (1.0 - rng.double()).ln();
+            if t >= exit {
+                return None;
+            }
+
+            let p = r.at(t);
+            if rng.double() * majorant < self.density * self.field.density(&p) {
+                // normal and facing mean nothing inside a volume
+                return Some(HitRecord::new(
+                    p,
+                    Vec3::new(1.0, 0.0, 0.0),
+                    t,
+                    (0.0, 0.0),
+                    true,
+                    self.phase.as_ref(),
+                ));
+            }
+        }
+    }
+}
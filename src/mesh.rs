@@ -0,0 +1,311 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::objects::{planar_pdf, HitRecord, Hittable, HittableList, Material};
+use crate::ray::Ray;
+use crate::vec3::{Color, Point, Vec3};
+
+/* Indices of one corner's attributes in the mesh's arrays */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vertex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+    pub color: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Face {
+    pub vertices: [Vertex; 3],
+    /* Index into the mesh's materials */
+    pub material: usize,
+}
+
+/* Shared vertex data and the faces indexing into it. Corners without a
+ * normal use the face's geometric normal; without texture coordinates they
+ * get the triangle's barycentric coordinates. Vertex colors, when every
+ * corner of a face has one, are interpolated into the hit record.
+ */
+pub struct Mesh {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub colors: Vec<Color>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<Arc<dyn Material>>,
+}
+
+/* One face of a mesh */
+pub struct Triangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: Arc<dyn Material>) -> Triangle {
+        let corner = |position| Vertex {
+            position,
+            normal: None,
+            uv: None,
+            color: None,
+        };
+
+        let mesh = Mesh {
+            positions: vec![a, b, c],
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            faces: vec![Face {
+                vertices: [corner(0), corner(1), corner(2)],
+                material: 0,
+            }],
+            materials: vec![material],
+        };
+
+        Triangle {
+            mesh: Arc::new(mesh),
+            face: 0,
+        }
+    }
+
+    fn corners(&self) -> (&Face, Point, Point, Point) {
+        let face = &self.mesh.faces[self.face];
+        let [a, b, c] = face.vertices;
+        let p = &self.mesh.positions;
+
+        (face, p[a.position], p[b.position], p[c.position])
+    }
+}
+
+/* Index of the largest component of `v` by magnitude */
+fn major_axis(v: &Vec3) -> usize {
+    let (x, y, z) = (v.x().abs(), v.y().abs(), v.z().abs());
+    if x > y && x > z {
+        0
+    } else if y > z {
+        1
+    } else {
+        2
+    }
+}
+
+impl Hittable for Triangle {
+    /* Watertight ray/triangle intersection (Woop, Benthin and Wald 2013):
+     * the vertices are moved into a space where the ray runs along +Z from
+     * the origin, so edges shared by two triangles are tested identically
+     * from both sides and rays cannot slip through between them.
+     */
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let (face, a, b, c) = self.corners();
+        let origin = *r.get_origin();
+        let d = r.get_direction();
+
+        let kz = major_axis(d);
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if d[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        let sx = d[kx] / d[kz];
+        let sy = d[ky] / d[kz];
+        let sz = 1.0 / d[kz];
+
+        let (pa, pb, pc) = (a - origin, b - origin, c - origin);
+        let ax = pa[kx] - sx * pa[kz];
+        let ay = pa[ky] - sy * pa[kz];
+        let bx = pb[kx] - sx * pb[kz];
+        let by = pb[ky] - sy * pb[kz];
+        let cx = pc[kx] - sx * pc[kz];
+        let cy = pc[ky] - sy * pc[kz];
+
+        // scaled barycentric coordinates, one per vertex
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return (false, None);
+        }
+
+        let determinant = u + v + w;
+        if determinant == 0.0 {
+            return (false, None);
+        }
+
+        let scaled_t = sz * (u * pa[kz] + v * pb[kz] + w * pc[kz]);
+        let root = scaled_t / determinant;
+        if !(root >= t.0 && root <= t.1) {
+            return (false, None);
+        }
+
+        let weights = [u / determinant, v / determinant, w / determinant];
+        let mesh = &self.mesh;
+
+        let geometric_normal = Vec3::cross_product(&(b - a), &(c - a)).unit();
+        let front_facing = Vec3::dot(d, &geometric_normal) < 0.0;
+
+        let mut shading_normal = geometric_normal;
+        if let [Some(na), Some(nb), Some(nc)] = face.vertices.map(|v| v.normal) {
+            let n = weights[0] * mesh.normals[na]
+                + weights[1] * mesh.normals[nb]
+                + weights[2] * mesh.normals[nc];
+            if !n.near_zero() {
+                shading_normal = n.unit();
+            }
+        }
+
+        let uv = match face.vertices.map(|v| v.uv) {
+            [Some(ta), Some(tb), Some(tc)] => {
+                let (ta, tb, tc) = (mesh.uvs[ta], mesh.uvs[tb], mesh.uvs[tc]);
+                (
+                    weights[0] * ta.0 + weights[1] * tb.0 + weights[2] * tc.0,
+                    weights[0] * ta.1 + weights[1] * tb.1 + weights[2] * tc.1,
+                )
+            }
+            _ => (weights[1], weights[2]),
+        };
+
+        // The side is decided by the geometric normal; the interpolated one
+        // is flipped along with it, and again if it still points into the
+        // surface, as it does when the vertex normals disagree with the
+        // winding.
+        let (oriented_geometric_normal, mut normal) = if front_facing {
+            (geometric_normal, shading_normal)
+        } else {
+            (-geometric_normal, -shading_normal)
+        };
+        if Vec3::dot(&normal, &oriented_geometric_normal) < 0.0 {
+            normal = -normal;
+        }
+
+        let mut rec = HitRecord::new(
+            r.at(root),
+            normal,
+            root,
+            uv,
+            front_facing,
+            mesh.materials[face.material].as_ref(),
+        );
+        if let [Some(ca), Some(cb), Some(cc)] = face.vertices.map(|v| v.color) {
+            rec.color = Some(
+                weights[0] * mesh.colors[ca]
+                    + weights[1] * mesh.colors[cb]
+                    + weights[2] * mesh.colors[cc],
+            );
+        }
+
+        (true, Some(rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (_, a, b, c) = self.corners();
+        let mut bounds = Aabb::new(a, b);
+        bounds.grow(&c);
+
+        Some(bounds.padded(1e-4))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        match self.hit(&ray, (0.001, f64::INFINITY)) {
+            (true, Some(rec)) => {
+                let (_, a, b, c) = self.corners();
+                let n = Vec3::cross_product(&(b - a), &(c - a));
+                planar_pdf(direction, rec.t, &n.unit(), 0.5 * n.length())
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        let (_, a, b, c) = self.corners();
+
+        // folding the unit square onto the triangle keeps points uniform
+        let s = u.0.sqrt();
+        let t = u.1;
+        let point = (1.0 - s) * a + s * (1.0 - t) * b + s * t * c;
+
+        point - *origin
+    }
+}
+
+/* All faces of a mesh behind a BVH of their own, so a mesh can be added to
+ * a scene as a single object.
+ */
+pub struct TriangleMesh {
+    triangles: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: Mesh) -> TriangleMesh {
+        let mesh = Arc::new(mesh);
+
+        let mut list = HittableList::new();
+        for face in 0..mesh.faces.len() {
+            list.add(Arc::new(Triangle {
+                mesh: mesh.clone(),
+                face,
+            }));
+        }
+
+        TriangleMesh {
+            triangles: Bvh::new(list),
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        self.triangles.hit(r, t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.triangles.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Lambertian;
+
+    #[test]
+    fn shading_normal_faces_the_ray_when_vertex_normals_oppose_the_winding() {
+        // wound counterclockwise seen from +z, with normals pointing to -z
+        let corner = |i| Vertex {
+            position: i,
+            normal: Some(0),
+            uv: None,
+            color: None,
+        };
+        let mesh = Mesh {
+            positions: vec![
+                Point::new(0.0, 0.0, 0.0),
+                Point::new(1.0, 0.0, 0.0),
+                Point::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![Vec3::new(0.0, 0.0, -1.0)],
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            faces: vec![Face {
+                vertices: [corner(0), corner(1), corner(2)],
+                material: 0,
+            }],
+            materials: vec![Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))],
+        };
+        let triangle = Triangle {
+            mesh: Arc::new(mesh),
+            face: 0,
+        };
+
+        for z in [1.0, -1.0] {
+            let ray = Ray::new(Point::new(0.25, 0.25, z), Vec3::new(0.0, 0.0, -z), 0.0);
+            let (_, record) = triangle.hit(&ray, (0.001, f64::INFINITY));
+            let record = record.expect("the ray hits the triangle");
+
+            assert_eq!(record.front_facing, z > 0.0);
+            assert!(Vec3::dot(&record.normal, ray.get_direction()) < 0.0);
+        }
+    }
+}
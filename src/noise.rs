@@ -0,0 +1,193 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::utility;
+use crate::vec3::{Point, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+/* How Perlin noise blends between lattice points. Plain trilinear blending
+ * leaves visible creases along the lattice, which Hermite smoothing of the
+ * weights hides.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Smoothing {
+    Trilinear,
+    Hermite,
+}
+
+/* Perlin gradient noise. Random gradients sit on the integer lattice and are
+ * blended by trilinear interpolation. The lattice is built from its own seed
+ * so that a noise pattern looks the same on every render.
+ */
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    permutations: [Vec<usize>; 3],
+    smoothing: Smoothing,
+}
+
+impl Perlin {
+    pub fn new(seed: u64, smoothing: Smoothing) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                let length_squared = v.length_squared();
+                if length_squared > 1e-6 && length_squared <= 1.0 {
+                    break v.unit();
+                }
+            })
+            .collect();
+
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let permutations = [permutation(), permutation(), permutation()];
+
+        Perlin {
+            gradients,
+            permutations,
+            smoothing,
+        }
+    }
+
+    /* Noise at `p`, roughly within [-1, 1] */
+    pub fn noise(&self, p: &Point) -> f64 {
+        let cell = [p.x().floor(), p.y().floor(), p.z().floor()];
+        let f = [p.x() - cell[0], p.y() - cell[1], p.z() - cell[2]];
+        let w = match self.smoothing {
+            Smoothing::Trilinear => f,
+            Smoothing::Hermite => f.map(|f| f * f * (3.0 - 2.0 * f)),
+        };
+
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = |axis: usize, d: usize| {
+                        self.permutations[axis][(cell[axis] as i64 + d as i64) as usize & 255]
+                    };
+                    let gradient = &self.gradients[index(0, di) ^ index(1, dj) ^ index(2, dk)];
+                    let offset = Vec3::new(f[0] - di as f64, f[1] - dj as f64, f[2] - dk as f64);
+
+                    let weight = |w: f64, d: usize| if d == 1 { w } else { 1.0 - w };
+                    sum += weight(w[0], di)
+                        * weight(w[1], dj)
+                        * weight(w[2], dk)
+                        * Vec3::dot(gradient, &offset);
+                }
+            }
+        }
+
+        sum
+    }
+
+    /* Fractal Brownian motion: `octaves` layers of noise, each at twice the
+     * frequency and half the amplitude of the one before.
+     */
+    pub fn fbm(&self, p: &Point, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut p = *p;
+        let mut amplitude = 1.0;
+
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&p);
+            amplitude *= 0.5;
+            p = 2.0 * p;
+        }
+
+        sum
+    }
+
+    /* Like `fbm` but summing the magnitude of every octave, which gives the
+     * sharp creases of turbulent flow. The result lies roughly in [0, 1].
+     */
+    pub fn turbulence(&self, p: &Point, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut p = *p;
+        let mut amplitude = 1.0;
+
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&p).abs();
+            amplitude *= 0.5;
+            p = 2.0 * p;
+        }
+
+        sum
+    }
+}
+
+/* Worley, or cellular, noise: every cell of the integer lattice holds one
+ * randomly placed feature point, and the noise is the distance to the
+ * nearest of them, giving a pattern of cells like cobbles or scales.
+ */
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Worley {
+        Worley { seed }
+    }
+
+    fn feature(&self, cell: [i64; 3]) -> Point {
+        let mut hash = utility::mix(self.seed);
+        for c in cell {
+            hash = utility::mix(hash ^ c as u64);
+        }
+
+        // three 21 bit fractions from the one hash
+        let fraction = |shift: u32| ((hash >> shift) & 0x1f_ffff) as f64 / (1 << 21) as f64;
+        Point::new(
+            cell[0] as f64 + fraction(0),
+            cell[1] as f64 + fraction(21),
+            cell[2] as f64 + fraction(42),
+        )
+    }
+
+    /* Distance from `p` to the nearest feature point, between 0 and about 1 */
+    pub fn noise(&self, p: &Point) -> f64 {
+        let cell = [p.x().floor(), p.y().floor(), p.z().floor()].map(|c| c as i64);
+        let fraction = [
+            p.x() - cell[0] as f64,
+            p.y() - cell[1] as f64,
+            p.z() - cell[2] as f64,
+        ];
+        let mut nearest = (self.feature(cell) - *p).length_squared();
+
+        // The cell's own feature point is at most sqrt(3) away, so a nearer
+        // one can be up to two cells over; of those, only cells closer than
+        // the nearest point found so far are looked at.
+        for dx in -2..=2 {
+            for dy in -2..=2 {
+                for dz in -2..=2 {
+                    let offset = [dx, dy, dz];
+                    let gap: f64 = (0..3)
+                        .map(|i| match offset[i] {
+                            o if o < 0 => fraction[i] - (o + 1) as f64,
+                            0 => 0.0,
+                            o => o as f64 - fraction[i],
+                        })
+                        .map(|d| d * d)
+                        .sum();
+                    if gap >= nearest {
+                        continue;
+                    }
+
+                    let feature = self.feature([cell[0] + dx, cell[1] + dy, cell[2] + dz]);
+                    nearest = nearest.min((feature - *p).length_squared());
+                }
+            }
+        }
+
+        nearest.sqrt()
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::mesh::{Face, Mesh, Vertex};
+use crate::objects::{Dielectric, Lambertian, Material, Metal};
+use crate::texture::{ColorSpace, Filter, ImageTexture, Texture, Wrap};
+use crate::vec3::{Color, Point, Vec3};
+
+/* Wavefront OBJ geometry and MTL materials. Polygons are split into
+ * triangle fans; groups, smoothing groups and free-form geometry are
+ * ignored. MTL materials are mapped as closely as our materials allow:
+ * transparent ones (`d` below 1, `Tr` above 0 or a refracting `illum`)
+ * become dielectrics with index `Ni`, ones with a stronger specular than
+ * diffuse color or `illum 3` become metals whose fuzz follows the Phong
+ * exponent `Ns`, and everything else is Lambertian with `Kd` or `map_Kd`.
+ */
+
+fn invalid(line: usize, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
+
+fn floats(line: usize, tokens: &[&str], count: usize) -> io::Result<Vec<f64>> {
+    if tokens.len() < count {
+        return Err(invalid(line, format!("expected {} numbers", count)));
+    }
+
+    tokens[..count]
+        .iter()
+        .map(|t| {
+            t.parse::<f64>()
+                .map_err(|_| invalid(line, format!("invalid number `{}`", t)))
+        })
+        .collect()
+}
+
+fn color(line: usize, tokens: &[&str]) -> io::Result<Color> {
+    let v = floats(line, tokens, 3)?;
+    Ok(Color::new(v[0], v[1], v[2]))
+}
+
+/* Resolves a 1-based, or negative and relative to the end, OBJ index */
+fn index(line: usize, token: &str, count: usize) -> io::Result<usize> {
+    let i = token
+        .parse::<i64>()
+        .map_err(|_| invalid(line, format!("invalid index `{}`", token)))?;
+
+    let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(invalid(line, format!("index {} out of range", i)));
+    }
+
+    Ok(resolved as usize)
+}
+
+struct MtlEntry {
+    diffuse: Color,
+    specular: Color,
+    shininess: f64,
+    ior: Option<f64>,
+    dissolve: Option<f64>,
+    illum: u32,
+    diffuse_map: Option<String>,
+}
+
+impl MtlEntry {
+    fn new() -> MtlEntry {
+        MtlEntry {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            ior: None,
+            dissolve: None,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+
+    fn material(&self, directory: &Path) -> io::Result<Arc<dyn Material>> {
+        let strongest = |c: &Color| c.x().max(c.y()).max(c.z());
+
+        let transparent =
+            self.dissolve.is_some_and(|d| d < 1.0) || matches!(self.illum, 4 | 6 | 7 | 9);
+        if transparent {
+            return Ok(Arc::new(Dielectric::new(self.ior.unwrap_or(1.5))));
+        }
+
+        if self.diffuse_map.is_none()
+            && (self.illum == 3 || strongest(&self.specular) > strongest(&self.diffuse))
+        {
+            // the usual mapping from a Phong exponent to a roughness
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            return Ok(Arc::new(Metal::new(self.specular, fuzz)));
+        }
+
+        match &self.diffuse_map {
+            Some(file) => {
+                let path = directory.join(file);
+                let texture: Arc<dyn Texture> = Arc::new(
+                    ImageTexture::load(&path, ColorSpace::Srgb, Filter::Bilinear, Wrap::Repeat)
+                        .map_err(|e| {
+                            io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+                        })?,
+                );
+                Ok(Arc::new(Lambertian::with_texture(texture)))
+            }
+            None => Ok(Arc::new(Lambertian::new(self.diffuse))),
+        }
+    }
+}
+
+fn load_mtl(path: &Path, materials: &mut HashMap<String, Arc<dyn Material>>) -> io::Result<()> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let source = fs::read_to_string(path)?;
+    let located = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+
+    let mut current: Option<(String, MtlEntry)> = None;
+    let mut finish = |current: Option<(String, MtlEntry)>| -> io::Result<()> {
+        if let Some((name, entry)) = current {
+            materials.insert(name, entry.material(directory)?);
+        }
+        Ok(())
+    };
+
+    for (number, raw) in source.lines().enumerate() {
+        let line = number + 1;
+        let content = raw.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = content.split_whitespace().collect();
+        let (keyword, args) = match tokens.split_first() {
+            Some((k, args)) => (*k, args),
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            finish(current.take()).map_err(located)?;
+            let name = args.join(" ");
+            current = Some((name, MtlEntry::new()));
+            continue;
+        }
+
+        let entry = match &mut current {
+            Some((_, entry)) => entry,
+            None => continue,
+        };
+
+        match keyword {
+            "Kd" => entry.diffuse = color(line, args).map_err(located)?,
+            "Ks" => entry.specular = color(line, args).map_err(located)?,
+            "Ns" => entry.shininess = floats(line, args, 1).map_err(located)?[0],
+            "Ni" => entry.ior = Some(floats(line, args, 1).map_err(located)?[0]),
+            "d" => entry.dissolve = Some(floats(line, args, 1).map_err(located)?[0]),
+            "Tr" => entry.dissolve = Some(1.0 - floats(line, args, 1).map_err(located)?[0]),
+            "illum" => {
+                entry.illum = floats(line, args, 1).map_err(located)?[0] as u32;
+            }
+            // options such as `-s 1 1 1` come before the file name
+            "map_Kd" => entry.diffuse_map = args.last().map(|s| s.to_string()),
+            _ => {}
+        }
+    }
+
+    finish(current.take()).map_err(located)
+}
+
+/* Loads an OBJ file. With `material` given every face uses it and material
+ * libraries are not read; otherwise faces use their `usemtl` material, or a
+ * plain gray one when there is none.
+ */
+pub fn load<P: AsRef<Path>>(path: P, material: Option<Arc<dyn Material>>) -> io::Result<Mesh> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let source = fs::read_to_string(path)?;
+
+    let mut mesh = Mesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        colors: Vec::new(),
+        faces: Vec::new(),
+        materials: vec![material
+            .clone()
+            .unwrap_or_else(|| Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))],
+    };
+
+    let mut library = HashMap::new();
+    // material names to their index in the mesh
+    let mut used: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    for (number, raw) in source.lines().enumerate() {
+        let line = number + 1;
+        let content = raw.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = content.split_whitespace().collect();
+        let (keyword, args) = match tokens.split_first() {
+            Some((k, args)) => (*k, args),
+            None => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let v = floats(line, args, 3)?;
+                mesh.positions.push(Point::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let v = floats(line, args, 3)?;
+                mesh.normals.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = floats(line, args, 1)?;
+                let v1 = if args.len() > 1 {
+                    floats(line, &args[1..], 1)?[0]
+                } else {
+                    0.0
+                };
+                mesh.uvs.push((v[0], v1));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(invalid(line, "faces need at least three vertices".into()));
+                }
+
+                let mut corners = Vec::with_capacity(args.len());
+                for corner in args {
+                    let mut parts = corner.split('/');
+                    let position = index(line, parts.next().unwrap_or(""), mesh.positions.len())?;
+                    let uv = match parts.next() {
+                        Some(t) if !t.is_empty() => Some(index(line, t, mesh.uvs.len())?),
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(n) if !n.is_empty() => Some(index(line, n, mesh.normals.len())?),
+                        _ => None,
+                    };
+
+                    corners.push(Vertex {
+                        position,
+                        normal,
+                        uv,
+                        color: None,
+                    });
+                }
+
+                for i in 1..corners.len() - 1 {
+                    mesh.faces.push(Face {
+                        vertices: [corners[0], corners[i], corners[i + 1]],
+                        material: current_material,
+                    });
+                }
+            }
+            "mtllib" if material.is_none() => {
+                for file in args {
+                    load_mtl(&directory.join(file), &mut library)?;
+                }
+            }
+            "usemtl" if material.is_none() => {
+                let name = args.join(" ");
+                current_material = match used.get(&name) {
+                    Some(&i) => i,
+                    None => {
+                        let m = library.get(&name).ok_or_else(|| {
+                            invalid(line, format!("undefined material `{}`", name))
+                        })?;
+                        mesh.materials.push(m.clone());
+                        used.insert(name, mesh.materials.len() - 1);
+                        mesh.materials.len() - 1
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
@@ -1,34 +1,122 @@
+use crate::aabb::Aabb;
+use crate::phase::PhaseFunction;
 use crate::ray::Ray;
+use crate::sampling::{self, Onb};
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::{Color, Point, Vec3};
-use std::rc::Rc;
+use std::f64::consts::PI;
+use std::sync::Arc;
 use std::vec::Vec;
 
-pub trait Material {
-    fn scatter(&self, r: &Ray, record: &HitRecord) -> (bool, Color, Ray);
+/* The kind of reflection a scattered direction came from */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Lobe {
+    /* Spread over all directions, like matte surfaces and media */
+    Diffuse,
+    /* Spread around a preferred direction, like brushed metal */
+    Glossy,
+    /* A single exact direction, like mirrors and glass */
+    Specular,
 }
 
-pub struct HitRecord {
+/* A direction picked by `Material::sample` */
+pub struct ScatterRecord {
+    /* The direction light continues in, not necessarily of unit length */
+    pub direction: Vec3,
+    /* How much of the light coming back along `direction` the path keeps:
+     * the material's `eval` divided by `pdf`.
+     */
+    pub weight: Color,
+    /* The density per unit solid angle of picking `direction`, zero for
+     * specular lobes, whose directions cannot be picked any other way.
+     */
+    pub pdf: f64,
+    pub lobe: Lobe,
+}
+
+impl ScatterRecord {
+    pub fn is_specular(&self) -> bool {
+        self.lobe == Lobe::Specular
+    }
+}
+
+/* Materials describe how light scatters at a hit point. Directions passed
+ * to `eval` and `pdf` are unit vectors pointing away from the hit point:
+ * `wi` towards where light comes from and `wo` towards where it leaves,
+ * back along the ray that found the point.
+ */
+pub trait Material: Send + Sync {
+    /* Picks a direction for the path to continue in from light leaving
+     * along `wo`, or None if the light is absorbed. The sample values `uc`
+     * in [0, 1) and `u` in [0, 1)^2 decide the choice: `uc` between lobes
+     * and `u` the direction within one.
+     */
+    fn sample(
+        &self,
+        record: &HitRecord,
+        wo: &Vec3,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord>;
+
+    /* The fraction of light arriving from `wi` that leaves along `wo`, per
+     * unit solid angle and including the cosine factor. Black for specular
+     * lobes, which reflect nothing from any direction they do not pick
+     * themselves.
+     */
+    fn eval(&self, _record: &HitRecord, _wi: &Vec3, _wo: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /* The density per unit solid angle with which `sample` picks `wi` for
+     * light leaving along `wo`, zero for specular lobes.
+     */
+    fn pdf(&self, _record: &HitRecord, _wi: &Vec3, _wo: &Vec3) -> f64 {
+        0.0
+    }
+
+    /* Radiance given off at the hit point, black for anything but lights */
+    fn emitted(&self, _record: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /* Whether objects of this material should be sampled as lights */
+    fn is_emissive(&self) -> bool {
+        false
+    }
+}
+
+pub struct HitRecord<'a> {
     pub point: Point,
     pub normal: Vec3,
     pub t: f64,
+    /* Surface coordinates of the hit point */
+    pub u: f64,
+    pub v: f64,
     pub front_facing: bool,
-    pub material: Rc<dyn Material>,
+    pub material: &'a dyn Material,
+    /* Interpolated vertex color, for meshes that have them */
+    pub color: Option<Color>,
 }
 
-impl HitRecord {
+impl<'a> HitRecord<'a> {
     pub fn new(
         point: Point,
         normal: Vec3,
         t: f64,
+        uv: (f64, f64),
         front_facing: bool,
-        material: Rc<dyn Material>,
-    ) -> HitRecord {
+        material: &'a dyn Material,
+    ) -> HitRecord<'a> {
         HitRecord {
             point,
             normal,
             t,
+            u: uv.0,
+            v: uv.1,
             front_facing,
             material,
+            color: None,
         }
     }
 
@@ -42,12 +130,45 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable {
-    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord>);
+pub trait Hittable: Send + Sync {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>);
+
+    /* None for objects without finite extent */
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /* The density per unit solid angle with which `random` picks
+     * `direction` from `origin`. Objects that cannot be sampled as lights
+     * leave this at zero.
+     */
+    fn pdf_value(&self, _origin: &Point, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /* A direction from `origin` towards the point of the object that the
+     * sample `u`, uniform in [0, 1)^2, maps to.
+     */
+    fn random(&self, _origin: &Point, _u: (f64, f64)) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+/* The density per unit solid angle of a point found `t` times along
+ * `direction` on a flat surface with unit `normal`, when points are picked
+ * uniformly over the surface's `area`.
+ */
+pub fn planar_pdf(direction: &Vec3, t: f64, normal: &Vec3, area: f64) -> f64 {
+    let length = direction.length();
+    let cosine = Vec3::dot(direction, normal).abs() / length;
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+
+    let distance = t * length;
+    distance * distance / (cosine * area)
 }
 
 pub struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
@@ -57,15 +178,30 @@ impl HittableList {
         }
     }
 
-    pub fn add(&mut self, obj: Rc<dyn Hittable>) {
+    pub fn add(&mut self, obj: Arc<dyn Hittable>) {
         self.objects.push(obj);
     }
 
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.objects.clear();
     }
 
-    pub fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord>) {
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Arc<dyn Hittable>> {
+        self.objects
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
         let mut hit = false;
         let mut closest = t.1;
 
@@ -88,66 +224,177 @@ impl HittableList {
             }
         }
 
-        return (hit, record);
+        (hit, record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut bounds = Aabb::empty();
+        for obj in &self.objects {
+            bounds = Aabb::surrounding(&bounds, &obj.bounding_box()?);
+        }
+
+        Some(bounds)
+    }
+
+    /* Samples one of the objects, each picked with equal probability */
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|obj| obj.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        let count = self.objects.len();
+        let scaled = u.0 * count as f64;
+        let index = (scaled as usize).min(count - 1);
+
+        // what is left of the sample after picking the object is again uniform
+        let remainder = (scaled - index as f64).min(1.0 - f64::EPSILON / 2.0);
+        self.objects[index].random(origin, (remainder, u.1))
+    }
+}
+
+/* A material's albedo at the hit point, tinted by the vertex color if any */
+fn albedo(texture: &dyn Texture, record: &HitRecord) -> Color {
+    let color = texture.value(record.u, record.v, &record.point);
+    match record.color {
+        Some(tint) => color * tint,
+        None => color,
     }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(a: Color) -> Lambertian {
-        Lambertian { albedo: a }
+        Lambertian::with_texture(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>) -> Lambertian {
+        Lambertian { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r: &Ray, record: &HitRecord) -> (bool, Color, Ray) {
-        let mut scatter_direction = record.normal + Vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = record.normal.clone();
-        }
+    fn sample(
+        &self,
+        record: &HitRecord,
+        _wo: &Vec3,
+        _uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let local = sampling::cosine_hemisphere(u);
+
+        Some(ScatterRecord {
+            direction: Onb::new(&record.normal).local(&local),
+            weight: albedo(self.albedo.as_ref(), record),
+            pdf: sampling::cosine_hemisphere_pdf(local.z()),
+            lobe: Lobe::Diffuse,
+        })
+    }
 
-        let ray = Ray::new(record.point, scatter_direction);
-        let attenuation = self.albedo.clone();
+    fn eval(&self, record: &HitRecord, wi: &Vec3, wo: &Vec3) -> Color {
+        albedo(self.albedo.as_ref(), record) * self.pdf(record, wi, wo)
+    }
 
-        return (true, attenuation, ray);
+    fn pdf(&self, record: &HitRecord, wi: &Vec3, _wo: &Vec3) -> f64 {
+        sampling::cosine_hemisphere_pdf(Vec3::dot(&record.normal, wi))
     }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(a: Color, f: f64) -> Metal {
+        Metal::with_texture(Arc::new(SolidColor::new(a)), f)
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>, f: f64) -> Metal {
         let mut fuzz = f;
         if fuzz > 1.0 {
             fuzz = 1.0;
         }
-        Metal {
-            albedo: a,
-            fuzz: fuzz,
-        }
+        Metal { albedo, fuzz }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r: &Ray, record: &HitRecord) -> (bool, Color, Ray) {
-        let reflected = Vec3::reflect(&r.get_direction().unit(), &record.normal);
-        let scattered = Ray::new(
-            record.point,
-            reflected + self.fuzz * Vec3::random_in_unit_sphere(),
-        );
-        let attenuation = self.albedo.clone();
+    fn sample(
+        &self,
+        record: &HitRecord,
+        wo: &Vec3,
+        uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let reflected = Vec3::reflect(&-*wo, &record.normal);
+        let ball = sampling::uniform_ball((u.0, u.1, uc));
+        let direction = reflected + self.fuzz * ball;
+        if Vec3::dot(&direction, &record.normal) <= 0.0 {
+            return None;
+        }
+
+        let (pdf, lobe) = if self.fuzz > 0.0 {
+            (self.pdf(record, &direction.unit(), wo), Lobe::Glossy)
+        } else {
+            (0.0, Lobe::Specular)
+        };
+
+        Some(ScatterRecord {
+            direction,
+            weight: albedo(self.albedo.as_ref(), record),
+            pdf,
+            lobe,
+        })
+    }
+
+    /* Paths only survive reflections above the surface, each carrying the
+     * albedo, so the light reflected from `wi` is the albedo times the
+     * density of picking it.
+     */
+    fn eval(&self, record: &HitRecord, wi: &Vec3, wo: &Vec3) -> Color {
+        if Vec3::dot(wi, &record.normal) <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        albedo(self.albedo.as_ref(), record) * self.pdf(record, wi, wo)
+    }
+
+    /* Directions run from the hit point through a ball of radius `fuzz`
+     * around the tip of the unit reflected vector, so the density is the
+     * ball's uniform density summed along the ray, t^2 dt from where it
+     * enters the ball to where it leaves.
+     */
+    fn pdf(&self, record: &HitRecord, wi: &Vec3, wo: &Vec3) -> f64 {
+        if self.fuzz <= 0.0 {
+            return 0.0;
+        }
+
+        let reflected = Vec3::reflect(&-*wo, &record.normal);
+        let cosine = Vec3::dot(wi, &reflected);
+        let discriminant = cosine * cosine - 1.0 + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+
+        let far = cosine + discriminant.sqrt();
+        let near = (cosine - discriminant.sqrt()).max(0.0);
+        if far <= 0.0 {
+            return 0.0;
+        }
 
-        return (
-            Vec3::dot(scattered.get_direction(), &record.normal) > 0.0,
-            attenuation,
-            scattered,
-        );
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
     }
 }
 
@@ -157,7 +404,7 @@ pub struct Dielectric {
 
 impl Dielectric {
     pub fn new(ir: f64) -> Dielectric {
-        Dielectric { ir: ir }
+        Dielectric { ir }
     }
 
     pub fn reflectance(cos: f64, refraction_index: f64) -> f64 {
@@ -168,14 +415,19 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r: &Ray, record: &HitRecord) -> (bool, Color, Ray) {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
+    fn sample(
+        &self,
+        record: &HitRecord,
+        wo: &Vec3,
+        uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
         let mut refraction_ratio = self.ir;
         if record.front_facing {
             refraction_ratio = 1.0 / self.ir;
         }
 
-        let unit_direction = r.get_direction().unit();
+        let unit_direction = -*wo;
         let mut cos_theta = Vec3::dot(&-unit_direction, &record.normal);
         if cos_theta > 1.0 {
             cos_theta = 1.0;
@@ -183,18 +435,100 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = (refraction_ratio * sin_theta) > 1.0;
-        let mut direction = Vec3::new(0.0, 0.0, 0.0);
 
-        if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > rand::random::<f64>()
-        {
-            direction = Vec3::reflect(&unit_direction, &record.normal);
-        } else {
-            direction = Vec3::refract(&unit_direction, &record.normal, refraction_ratio);
-        }
+        let direction =
+            if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > uc {
+                Vec3::reflect(&unit_direction, &record.normal)
+            } else {
+                Vec3::refract(&unit_direction, &record.normal, refraction_ratio)
+            };
+
+        Some(ScatterRecord {
+            direction,
+            weight: Color::new(1.0, 1.0, 1.0),
+            pdf: 0.0,
+            lobe: Lobe::Specular,
+        })
+    }
+}
 
-        let scattered = Ray::new(record.point, direction);
+/* The material of media such as fog and smoke: scatters in a direction
+ * chosen by its phase function, tinted by the albedo.
+ */
+pub struct Volumetric {
+    albedo: Arc<dyn Texture>,
+    phase: Arc<dyn PhaseFunction>,
+}
+
+impl Volumetric {
+    pub fn new(a: Color, phase: Arc<dyn PhaseFunction>) -> Volumetric {
+        Volumetric::with_texture(Arc::new(SolidColor::new(a)), phase)
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>, phase: Arc<dyn PhaseFunction>) -> Volumetric {
+        Volumetric { albedo, phase }
+    }
+}
+
+impl Material for Volumetric {
+    fn sample(
+        &self,
+        record: &HitRecord,
+        wo: &Vec3,
+        _uc: f64,
+        u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        let direction = self.phase.sample(&-*wo, u);
+
+        Some(ScatterRecord {
+            direction,
+            weight: self.albedo.value(record.u, record.v, &record.point),
+            pdf: self.phase.pdf(&-*wo, &direction),
+            lobe: Lobe::Diffuse,
+        })
+    }
+
+    fn eval(&self, record: &HitRecord, wi: &Vec3, wo: &Vec3) -> Color {
+        self.albedo.value(record.u, record.v, &record.point) * self.pdf(record, wi, wo)
+    }
+
+    fn pdf(&self, _record: &HitRecord, wi: &Vec3, wo: &Vec3) -> f64 {
+        self.phase.pdf(&-*wo, wi)
+    }
+}
+
+/* Emits light from both sides of a surface and reflects none. */
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+    intensity: f64,
+}
+
+impl DiffuseLight {
+    pub fn new(color: Color, intensity: f64) -> DiffuseLight {
+        DiffuseLight::with_texture(Arc::new(SolidColor::new(color)), intensity)
+    }
+
+    pub fn with_texture(emit: Arc<dyn Texture>, intensity: f64) -> DiffuseLight {
+        DiffuseLight { emit, intensity }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(
+        &self,
+        _record: &HitRecord,
+        _wo: &Vec3,
+        _uc: f64,
+        _u: (f64, f64),
+    ) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        self.intensity * self.emit.value(record.u, record.v, &record.point)
+    }
 
-        return (true, attenuation, scattered);
+    fn is_emissive(&self) -> bool {
+        true
     }
 }
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::exr;
+use crate::framebuffer::Framebuffer;
+use crate::hdr;
+use crate::pfm;
+use crate::png;
+use crate::ppm;
+use crate::tonemap::ToneMapping;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Ppm,
+    PpmAscii,
+    Png,
+    Hdr,
+    Pfm,
+    Exr,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Format::Ppm),
+            "ppm-ascii" => Some(Format::PpmAscii),
+            "png" => Some(Format::Png),
+            "hdr" => Some(Format::Hdr),
+            "pfm" => Some(Format::Pfm),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    /* Floating point formats store the linear framebuffer values as they
+     * are; the others hold display-ready, quantized values.
+     */
+    pub fn is_high_dynamic_range(&self) -> bool {
+        matches!(self, Format::Hdr | Format::Pfm | Format::Exr)
+    }
+
+    /* Picks the format from the file extension; `.ppm` files are binary. */
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Format::Ppm),
+            "png" => Some(Format::Png),
+            "hdr" => Some(Format::Hdr),
+            "pfm" => Some(Format::Pfm),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+}
+
+pub fn write<W: Write>(
+    out: &mut W,
+    framebuffer: &Framebuffer,
+    format: Format,
+    bit_depth: u8,
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+
+    if format.is_high_dynamic_range() {
+        let pixels = framebuffer.pixels();
+        match format {
+            Format::Hdr => hdr::write(out, width, height, pixels)?,
+            Format::Pfm => pfm::write(out, width, height, pixels)?,
+            _ => exr::write(out, width, height, pixels)?,
+        }
+        return out.flush();
+    }
+
+    let max_value = if bit_depth == 16 { u16::MAX } else { 255 };
+    let samples = framebuffer.display_samples(max_value, tone_mapping);
+
+    match format {
+        Format::Ppm => ppm::write(out, width, height, &samples, max_value)?,
+        Format::PpmAscii => ppm::write_ascii(out, width, height, &samples, max_value)?,
+        _ => png::write(out, width, height, &samples, bit_depth)?,
+    }
+
+    out.flush()
+}
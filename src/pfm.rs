@@ -0,0 +1,29 @@
+use std::io::{self, Write};
+
+use crate::vec3::Color;
+
+/* Writes a color Portable Float Map from linear colors given row by row from
+ * the top. PFM stores rows bottom to top; the negative scale marks the data
+ * as little-endian.
+ */
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    let mut data = Vec::with_capacity(width * 12);
+    for row in pixels.chunks(width).rev() {
+        data.clear();
+        for pixel in row {
+            data.extend_from_slice(&(pixel.x() as f32).to_le_bytes());
+            data.extend_from_slice(&(pixel.y() as f32).to_le_bytes());
+            data.extend_from_slice(&(pixel.z() as f32).to_le_bytes());
+        }
+        out.write_all(&data)?;
+    }
+
+    Ok(())
+}
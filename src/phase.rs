@@ -0,0 +1,81 @@
+use std::f64::consts::PI;
+
+use crate::sampling::{self, Onb};
+use crate::utility;
+use crate::vec3::Vec3;
+
+/* How light traveling through a medium is redirected when it scatters */
+pub trait PhaseFunction: Send + Sync {
+    /* Picks the unit direction light continues in after scattering while
+     * traveling along the unit vector `direction`, as decided by the sample
+     * `u` in [0, 1)^2.
+     */
+    fn sample(&self, direction: &Vec3, u: (f64, f64)) -> Vec3;
+
+    /* The density per unit solid angle with which `sample` picks the unit
+     * vector `scattered` for light traveling along `direction`.
+     */
+    fn pdf(&self, direction: &Vec3, scattered: &Vec3) -> f64;
+}
+
+/* Scatters equally in all directions */
+pub struct Isotropic;
+
+impl PhaseFunction for Isotropic {
+    fn sample(&self, _direction: &Vec3, u: (f64, f64)) -> Vec3 {
+        sampling::uniform_sphere(u)
+    }
+
+    fn pdf(&self, _direction: &Vec3, _scattered: &Vec3) -> f64 {
+        sampling::uniform_sphere_pdf()
+    }
+}
+
+/* The Henyey-Greenstein phase function. The asymmetry `g`, between -1 and 1,
+ * is the average cosine of the scattering angle: positive values scatter
+ * mostly forward like haze and clouds, negative ones mostly back, and 0 is
+ * isotropic.
+ */
+pub struct HenyeyGreenstein {
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {
+            g: utility::clamp(g, -0.99, 0.99),
+        }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn sample(&self, direction: &Vec3, u: (f64, f64)) -> Vec3 {
+        let g = self.g;
+        let xi = u.0;
+
+        // inverting the cumulative distribution of the scattering angle
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let cos_theta = utility::clamp(cos_theta, -1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * u.1;
+
+        Onb::new(direction).local(&Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+
+    fn pdf(&self, direction: &Vec3, scattered: &Vec3) -> f64 {
+        let g = self.g;
+        let cos_theta = Vec3::dot(direction, scattered);
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
@@ -0,0 +1,364 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::mesh::{Face, Mesh, Vertex};
+use crate::objects::{Lambertian, Material};
+use crate::texture::srgb_decode;
+use crate::vec3::{Color, Point, Vec3};
+
+/* Stanford PLY meshes in ASCII or either binary byte order. Vertices may
+ * carry normals (`nx ny nz`), texture coordinates (`u v`, `s t` or
+ * `texture_u texture_v`) and colors (`red green blue`, 8 bit sRGB when
+ * stored as integers, linear otherwise); faces are polygons listed in
+ * `vertex_indices` or `vertex_index` and split into triangle fans. Other
+ * elements and properties are skipped.
+ */
+
+fn invalid<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /* The largest value of an integer type, which stands for full
+     * intensity in a color channel of that type.
+     */
+    fn max_value(&self) -> Option<f64> {
+        match self {
+            Scalar::I8 => Some(i8::MAX as f64),
+            Scalar::U8 => Some(u8::MAX as f64),
+            Scalar::I16 => Some(i16::MAX as f64),
+            Scalar::U16 => Some(u16::MAX as f64),
+            Scalar::I32 => Some(i32::MAX as f64),
+            Scalar::U32 => Some(u32::MAX as f64),
+            Scalar::F32 | Scalar::F64 => None,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+/* Reads the values following the header one at a time */
+struct Values<'a> {
+    data: &'a [u8],
+    position: usize,
+    encoding: Encoding,
+}
+
+impl<'a> Values<'a> {
+    fn ascii_token(&mut self) -> io::Result<&'a str> {
+        while self.position < self.data.len() && self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(invalid("unexpected end of PLY data"));
+        }
+
+        std::str::from_utf8(&self.data[start..self.position])
+            .map_err(|_| invalid("malformed PLY data"))
+    }
+
+    fn read(&mut self, scalar: Scalar) -> io::Result<f64> {
+        if self.encoding == Encoding::Ascii {
+            let token = self.ascii_token()?;
+            return token
+                .parse::<f64>()
+                .map_err(|_| invalid(format!("invalid number `{}` in PLY data", token)));
+        }
+
+        let size = scalar.size();
+        let bytes = self
+            .data
+            .get(self.position..self.position + size)
+            .ok_or_else(|| invalid("truncated PLY data"))?;
+        self.position += size;
+
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(bytes);
+        if self.encoding == Encoding::BigEndian {
+            b[..size].reverse();
+        }
+
+        Ok(match scalar {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        })
+    }
+
+    fn read_index(&mut self, scalar: Scalar) -> io::Result<usize> {
+        let value = self.read(scalar)?;
+        if value < 0.0 || value.fract() != 0.0 {
+            return Err(invalid(format!("invalid PLY index {}", value)));
+        }
+
+        Ok(value as usize)
+    }
+}
+
+/* Splits the header off the data, returning the encoding, the elements
+ * and where the data starts.
+ */
+fn header(data: &[u8]) -> io::Result<(Encoding, Vec<Element>, usize)> {
+    let end = data
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .ok_or_else(|| invalid("missing PLY end_header"))?;
+    let text = std::str::from_utf8(&data[..end]).map_err(|_| invalid("malformed PLY header"))?;
+
+    // the data starts after the end_header line's newline
+    let mut start = end + 10;
+    while start < data.len() && data[start] != b'\n' {
+        start += 1;
+    }
+    start += 1;
+
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid("not a PLY file"));
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", kind, _] => {
+                encoding = Some(match *kind {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(invalid(format!("unknown PLY format `{}`", kind))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid PLY element count `{}`", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let count = Scalar::from_name(count)
+                    .ok_or_else(|| invalid(format!("unknown PLY type `{}`", count)))?;
+                let item = Scalar::from_name(item)
+                    .ok_or_else(|| invalid(format!("unknown PLY type `{}`", item)))?;
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("PLY property outside of an element"))?
+                    .properties
+                    .push(Property::List(name.to_string(), count, item));
+            }
+            ["property", kind, name] => {
+                let kind = Scalar::from_name(kind)
+                    .ok_or_else(|| invalid(format!("unknown PLY type `{}`", kind)))?;
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("PLY property outside of an element"))?
+                    .properties
+                    .push(Property::Scalar(name.to_string(), kind));
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("malformed PLY header line `{}`", line))),
+        }
+    }
+
+    let encoding = encoding.ok_or_else(|| invalid("missing PLY format"))?;
+    Ok((encoding, elements, start))
+}
+
+/* Loads a PLY mesh. Faces use `material` if given, otherwise a Lambertian
+ * one that is white when the vertices have colors and gray when not.
+ */
+pub fn load<P: AsRef<Path>>(path: P, material: Option<Arc<dyn Material>>) -> io::Result<Mesh> {
+    let data = fs::read(path)?;
+    let (encoding, elements, start) = header(&data)?;
+
+    let mut values = Values {
+        data: &data,
+        position: start,
+        encoding,
+    };
+
+    let mut mesh = Mesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        colors: Vec::new(),
+        faces: Vec::new(),
+        materials: Vec::new(),
+    };
+
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut position = [0.0; 3];
+            let mut normal = [None; 3];
+            let mut uv = [None; 2];
+            let mut color = [None; 3];
+            let mut indices = Vec::new();
+
+            for property in &element.properties {
+                match property {
+                    Property::Scalar(name, kind) => {
+                        let value = values.read(*kind)?;
+                        if element.name != "vertex" {
+                            continue;
+                        }
+
+                        // integer colors are sRGB scaled to the type's range,
+                        // floating point ones linear
+                        let channel = |v: f64| match kind.max_value() {
+                            Some(max) => srgb_decode(v.max(0.0) / max),
+                            None => v,
+                        };
+                        match name.as_str() {
+                            "x" => position[0] = value,
+                            "y" => position[1] = value,
+                            "z" => position[2] = value,
+                            "nx" => normal[0] = Some(value),
+                            "ny" => normal[1] = Some(value),
+                            "nz" => normal[2] = Some(value),
+                            "u" | "s" | "texture_u" => uv[0] = Some(value),
+                            "v" | "t" | "texture_v" => uv[1] = Some(value),
+                            "red" => color[0] = Some(channel(value)),
+                            "green" => color[1] = Some(channel(value)),
+                            "blue" => color[2] = Some(channel(value)),
+                            _ => {}
+                        }
+                    }
+                    Property::List(name, count, item) => {
+                        let count = values.read_index(*count)?;
+                        let is_face_indices = element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index");
+                        for _ in 0..count {
+                            if is_face_indices {
+                                indices.push(values.read_index(*item)?);
+                            } else {
+                                values.read(*item)?;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                mesh.positions
+                    .push(Point::new(position[0], position[1], position[2]));
+                if let [Some(x), Some(y), Some(z)] = normal {
+                    mesh.normals.push(Vec3::new(x, y, z));
+                }
+                if let [Some(u), Some(v)] = uv {
+                    mesh.uvs.push((u, v));
+                }
+                if let [Some(r), Some(g), Some(b)] = color {
+                    mesh.colors.push(Color::new(r, g, b));
+                }
+            } else if element.name == "face" {
+                if indices.len() < 3 {
+                    return Err(invalid("PLY faces need at least three vertices"));
+                }
+                for i in 1..indices.len() - 1 {
+                    let corners = [indices[0], indices[i], indices[i + 1]];
+                    mesh.faces.push(Face {
+                        vertices: corners.map(|position| Vertex {
+                            position,
+                            normal: None,
+                            uv: None,
+                            color: None,
+                        }),
+                        material: 0,
+                    });
+                }
+            }
+        }
+    }
+
+    // Attributes are per vertex, so they share the position's index. The
+    // faces may come before the vertices, so this is done at the end.
+    let count = mesh.positions.len();
+    let has_normals = mesh.normals.len() == count;
+    let has_uvs = mesh.uvs.len() == count;
+    let has_colors = mesh.colors.len() == count;
+    for face in &mut mesh.faces {
+        for vertex in &mut face.vertices {
+            if vertex.position >= count {
+                return Err(invalid(format!(
+                    "PLY vertex index {} out of range",
+                    vertex.position
+                )));
+            }
+            vertex.normal = Some(vertex.position).filter(|_| has_normals);
+            vertex.uv = Some(vertex.position).filter(|_| has_uvs);
+            vertex.color = Some(vertex.position).filter(|_| has_colors);
+        }
+    }
+
+    let albedo = if has_colors { 1.0 } else { 0.5 };
+    mesh.materials.push(
+        material.unwrap_or_else(|| Arc::new(Lambertian::new(Color::new(albedo, albedo, albedo)))),
+    );
+
+    Ok(mesh)
+}
@@ -0,0 +1,298 @@
+use std::io::{self, Write};
+
+use crate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(&crc_input)?;
+    out.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/* Applies PNG filter `kind` to `row` given the unfiltered row above it. */
+fn filter(kind: u8, row: &[u8], above: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    out.push(kind);
+
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = above[i];
+        let c = if i >= bpp { above[i - bpp] } else { 0 };
+
+        let predicted = match kind {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+/* Writes an RGB PNG. `samples` holds three values per pixel, row by row from
+ * the top, each at most 255 for a bit depth of 8 or 65535 for 16.
+ */
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    samples: &[u16],
+    bit_depth: u8,
+) -> io::Result<()> {
+    let bpp = 3 * bit_depth as usize / 8;
+    let stride = width * bpp;
+
+    let mut raw = Vec::with_capacity(height * stride);
+    for &s in samples {
+        if bit_depth == 16 {
+            raw.extend_from_slice(&s.to_be_bytes());
+        } else {
+            raw.push(s as u8);
+        }
+    }
+
+    // Pick the filter per row that minimizes the sum of absolute residuals,
+    // the usual heuristic for photographic content.
+    let zero_row = vec![0; stride];
+    let mut filtered = Vec::with_capacity(height * (stride + 1));
+    let mut candidate = Vec::with_capacity(stride + 1);
+    for y in 0..height {
+        let row = &raw[y * stride..(y + 1) * stride];
+        let above = if y == 0 {
+            &zero_row[..]
+        } else {
+            &raw[(y - 1) * stride..y * stride]
+        };
+
+        let mut best = Vec::new();
+        let mut best_score = u64::MAX;
+        for kind in 0..5 {
+            candidate.clear();
+            filter(kind, row, above, bpp, &mut candidate);
+
+            let score = candidate[1..]
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum();
+            if score < best_score {
+                best_score = score;
+                best = candidate.clone();
+            }
+        }
+        filtered.extend_from_slice(&best);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth, truecolor, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[bit_depth, 2, 0, 0, 0]);
+
+    out.write_all(&SIGNATURE)?;
+    write_chunk(out, b"IHDR", &header)?;
+    write_chunk(out, b"IDAT", &zlib::compress(&filtered))?;
+    write_chunk(out, b"IEND", &[])
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn unfilter(kind: u8, row: &mut [u8], above: &[u8], bpp: usize) -> io::Result<()> {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = above[i];
+        let c = if i >= bpp { above[i - bpp] } else { 0 };
+
+        let predicted = match kind {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            4 => paeth(a, b, c),
+            _ => return Err(invalid("invalid PNG filter type")),
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+
+    Ok(())
+}
+
+/* Decodes a non-interlaced PNG of any color type into (width, height,
+ * samples, max value) with three samples per pixel, row by row from the
+ * top. Alpha is dropped and palettes are expanded.
+ */
+pub fn read(data: &[u8]) -> io::Result<(usize, usize, Vec<u16>, u16)> {
+    if data.len() < 8 || data[..8] != SIGNATURE {
+        return Err(invalid("not a PNG image"));
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut position = 8;
+
+    loop {
+        let length_bytes = data
+            .get(position..position + 4)
+            .ok_or_else(|| invalid("truncated PNG chunk"))?;
+        let length = u32::from_be_bytes([
+            length_bytes[0],
+            length_bytes[1],
+            length_bytes[2],
+            length_bytes[3],
+        ]) as usize;
+        let kind = data
+            .get(position + 4..position + 8)
+            .ok_or_else(|| invalid("truncated PNG chunk"))?;
+        let body = data
+            .get(position + 8..position + 8 + length)
+            .ok_or_else(|| invalid("truncated PNG chunk"))?;
+        position += 12 + length;
+
+        match kind {
+            b"IHDR" => {
+                if body.len() != 13 {
+                    return Err(invalid("malformed PNG header"));
+                }
+                header = Some((
+                    u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize,
+                    u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize,
+                    body[8],
+                    body[9],
+                    body[12],
+                ));
+            }
+            b"PLTE" => palette = body.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let (width, height, bit_depth, color_type, interlace) =
+        header.ok_or_else(|| invalid("missing PNG header"))?;
+    if width == 0 || height == 0 {
+        return Err(invalid("empty PNG image"));
+    }
+    if interlace != 0 {
+        return Err(invalid("interlaced PNG images are not supported"));
+    }
+
+    let channels = match (color_type, bit_depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) => 1,
+        (2, 8) | (2, 16) => 3,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (4, 8) | (4, 16) => 2,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(invalid("invalid PNG color type and bit depth")),
+    };
+    if color_type == 3 && palette.is_empty() {
+        return Err(invalid("palette PNG without a palette"));
+    }
+
+    let bits_per_pixel = channels * bit_depth as usize;
+    let stride = (width * bits_per_pixel).div_ceil(8);
+    let bpp = bits_per_pixel.div_ceil(8).max(1);
+
+    let size = height
+        .checked_mul(stride + 1)
+        .ok_or_else(|| invalid("PNG dimensions too large"))?;
+    let mut raw = zlib::decompress(&compressed)?;
+    if raw.len() < size {
+        return Err(invalid("truncated PNG image data"));
+    }
+
+    let mut rows = vec![0u8; size - height];
+    let zero_row = vec![0u8; stride];
+    for y in 0..height {
+        let line = &mut raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        let (kind, row) = line.split_at_mut(1);
+
+        let (done, rest) = rows.split_at_mut(y * stride);
+        let above = if y == 0 {
+            &zero_row[..]
+        } else {
+            &done[(y - 1) * stride..]
+        };
+        unfilter(kind[0], row, above, bpp)?;
+        rest[..stride].copy_from_slice(row);
+    }
+
+    // Reads the `index`th value of a row, whatever the bit depth.
+    let value = |row: &[u8], index: usize| -> u16 {
+        match bit_depth {
+            16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+            8 => row[index] as u16,
+            _ => {
+                let bit = index * bit_depth as usize;
+                let shift = 8 - bit_depth as usize - bit % 8;
+                ((row[bit / 8] >> shift) & ((1u8 << bit_depth) - 1)) as u16
+            }
+        }
+    };
+
+    let max_value = match (color_type, bit_depth) {
+        (3, _) | (_, 8) => 255,
+        (_, 16) => u16::MAX,
+        _ => (1u16 << bit_depth) - 1,
+    };
+
+    let mut samples = Vec::with_capacity(width * height * 3);
+    for row in rows.chunks(stride) {
+        for x in 0..width {
+            match color_type {
+                0 | 4 => {
+                    let gray = value(row, x * channels);
+                    samples.extend_from_slice(&[gray, gray, gray]);
+                }
+                3 => {
+                    let index = value(row, x) as usize;
+                    let entry = palette
+                        .get(3 * index..3 * index + 3)
+                        .ok_or_else(|| invalid("PNG palette index out of range"))?;
+                    samples.extend(entry.iter().map(|&v| v as u16));
+                }
+                _ => {
+                    for c in 0..3 {
+                        samples.push(value(row, x * channels + c));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((width, height, samples, max_value))
+}
@@ -0,0 +1,134 @@
+use std::io::{self, Write};
+
+/* Writes a binary (P6) PPM. `samples` holds three values per pixel, row by
+ * row from the top, up to `max_value`, which must be below 65536.
+ */
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    samples: &[u16],
+    max_value: u16,
+) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n{}\n", width, height, max_value)?;
+
+    let mut data = Vec::with_capacity(samples.len() * 2);
+    for &s in samples {
+        if max_value > 255 {
+            data.extend_from_slice(&s.to_be_bytes());
+        } else {
+            data.push(s as u8);
+        }
+    }
+
+    out.write_all(&data)
+}
+
+/* Writes a plain text (P3) PPM, one pixel per line. */
+pub fn write_ascii<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    samples: &[u16],
+    max_value: u16,
+) -> io::Result<()> {
+    writeln!(out, "P3\n{} {}\n{}", width, height, max_value)?;
+
+    for pixel in samples.chunks(3) {
+        writeln!(out, "{} {} {}", pixel[0], pixel[1], pixel[2])?;
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/* Reads the next whitespace separated header token, skipping comments. */
+fn header_token<'a>(data: &'a [u8], position: &mut usize) -> io::Result<&'a [u8]> {
+    loop {
+        while *position < data.len() && data[*position].is_ascii_whitespace() {
+            *position += 1;
+        }
+        if *position < data.len() && data[*position] == b'#' {
+            while *position < data.len() && data[*position] != b'\n' {
+                *position += 1;
+            }
+            continue;
+        }
+        break;
+    }
+
+    let start = *position;
+    while *position < data.len() && !data[*position].is_ascii_whitespace() {
+        *position += 1;
+    }
+    if start == *position {
+        return Err(invalid("unexpected end of PPM data"));
+    }
+
+    Ok(&data[start..*position])
+}
+
+fn header_number(data: &[u8], position: &mut usize) -> io::Result<usize> {
+    let token = header_token(data, position)?;
+    std::str::from_utf8(token)
+        .ok()
+        .and_then(|t| t.parse::<usize>().ok())
+        .ok_or_else(|| invalid("malformed number in PPM data"))
+}
+
+/* Decodes a P3 or P6 PPM into (width, height, samples, max value) with three
+ * samples per pixel, row by row from the top.
+ */
+pub fn read(data: &[u8]) -> io::Result<(usize, usize, Vec<u16>, u16)> {
+    let mut position = 0;
+    let binary = match header_token(data, &mut position)? {
+        b"P3" => false,
+        b"P6" => true,
+        _ => return Err(invalid("not a P3 or P6 PPM image")),
+    };
+
+    let width = header_number(data, &mut position)?;
+    let height = header_number(data, &mut position)?;
+    let max_value = header_number(data, &mut position)?;
+    if width == 0 || height == 0 || max_value == 0 || max_value > u16::MAX as usize {
+        return Err(invalid("invalid PPM dimensions or maximum value"));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| invalid("PPM dimensions too large"))?;
+    let mut samples = Vec::new();
+
+    if binary {
+        // exactly one whitespace byte separates the header from the data
+        let start = position + 1;
+        let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+        let end = count
+            .checked_mul(bytes_per_sample)
+            .and_then(|n| n.checked_add(start))
+            .ok_or_else(|| invalid("PPM dimensions too large"))?;
+        let body = data
+            .get(start..end)
+            .ok_or_else(|| invalid("truncated PPM data"))?;
+
+        if bytes_per_sample == 2 {
+            samples.extend(body.chunks(2).map(|b| u16::from_be_bytes([b[0], b[1]])));
+        } else {
+            samples.extend(body.iter().map(|&b| b as u16));
+        }
+    } else {
+        for _ in 0..count {
+            let sample = header_number(data, &mut position)?;
+            if sample > max_value {
+                return Err(invalid("PPM sample exceeds the maximum value"));
+            }
+            samples.push(sample as u16);
+        }
+    }
+
+    Ok((width, height, samples, max_value as u16))
+}
@@ -3,6 +3,8 @@ use crate::vec3;
 pub struct Ray {
     origin: vec3::Point,
     direction: vec3::Vec3,
+    /* The moment within the shutter interval the ray was sent at */
+    time: f64,
 }
 
 /* P(t) = A + tb
@@ -12,8 +14,12 @@ pub struct Ray {
  * t is a real number - the ray parameter
  */
 impl Ray {
-    pub fn new(origin: vec3::Point, direction: vec3::Vec3) -> Ray {
-        Ray { origin, direction }
+    pub fn new(origin: vec3::Point, direction: vec3::Vec3, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn get_origin(&self) -> &vec3::Point {
@@ -24,6 +30,10 @@ impl Ray {
         &self.direction
     }
 
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
     pub fn at(&self, t: f64) -> vec3::Point {
         self.origin + (t * self.direction)
     }
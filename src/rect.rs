@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::objects::{planar_pdf, HitRecord, Hittable, Material};
+use crate::ray::Ray;
+use crate::vec3::{Point, Vec3};
+
+/* The coordinate plane a rectangle lies in */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Plane {
+    Xy,
+    Xz,
+    Yz,
+}
+
+impl Plane {
+    /* The two in-plane axes followed by the normal axis */
+    fn axes(&self) -> (usize, usize, usize) {
+        match self {
+            Plane::Xy => (0, 1, 2),
+            Plane::Xz => (0, 2, 1),
+            Plane::Yz => (1, 2, 0),
+        }
+    }
+}
+
+fn axis_vector(axis: usize, value: f64) -> Vec3 {
+    let mut v = [0.0; 3];
+    v[axis] = value;
+    Vec3::new(v[0], v[1], v[2])
+}
+
+/* A rectangle in one of the coordinate planes at offset `k` along the
+ * remaining axis, facing towards that axis' positive direction. Cheaper to
+ * intersect than a general `Quad`.
+ */
+pub struct AxisRect {
+    plane: Plane,
+    min: (f64, f64),
+    max: (f64, f64),
+    k: f64,
+    material: Arc<dyn Material>,
+}
+
+impl AxisRect {
+    pub fn new(
+        plane: Plane,
+        a: (f64, f64),
+        b: (f64, f64),
+        k: f64,
+        material: Arc<dyn Material>,
+    ) -> AxisRect {
+        AxisRect {
+            plane,
+            min: (a.0.min(b.0), a.1.min(b.1)),
+            max: (a.0.max(b.0), a.1.max(b.1)),
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for AxisRect {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let (a, b, n) = self.plane.axes();
+        let origin = r.get_origin();
+        let direction = r.get_direction();
+
+        // also rejects the NaN of a ray running inside the plane
+        let root = (self.k - origin[n]) / direction[n];
+        if !(root >= t.0 && root <= t.1) {
+            return (false, None);
+        }
+
+        let x = origin[a] + root * direction[a];
+        let y = origin[b] + root * direction[b];
+        if x < self.min.0 || x > self.max.0 || y < self.min.1 || y > self.max.1 {
+            return (false, None);
+        }
+
+        let uv = (
+            (x - self.min.0) / (self.max.0 - self.min.0),
+            (y - self.min.1) / (self.max.1 - self.min.1),
+        );
+        let outward_normal = axis_vector(n, 1.0);
+
+        let mut rec = HitRecord::new(
+            r.at(root),
+            outward_normal,
+            root,
+            uv,
+            false,
+            self.material.as_ref(),
+        );
+        rec.set_face_normal(r, outward_normal);
+
+        (true, Some(rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (a, b, n) = self.plane.axes();
+        let minimum =
+            axis_vector(a, self.min.0) + axis_vector(b, self.min.1) + axis_vector(n, self.k);
+        let maximum =
+            axis_vector(a, self.max.0) + axis_vector(b, self.max.1) + axis_vector(n, self.k);
+
+        Some(Aabb::new(minimum, maximum).padded(1e-4))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        match self.hit(&ray, (0.001, f64::INFINITY)) {
+            (true, Some(rec)) => {
+                let area = (self.max.0 - self.min.0) * (self.max.1 - self.min.1);
+                let normal = axis_vector(self.plane.axes().2, 1.0);
+                planar_pdf(direction, rec.t, &normal, area)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        let (a, b, n) = self.plane.axes();
+        let point = axis_vector(a, self.min.0 + u.0 * (self.max.0 - self.min.0))
+            + axis_vector(b, self.min.1 + u.1 * (self.max.1 - self.min.1))
+            + axis_vector(n, self.k);
+
+        point - *origin
+    }
+}
+
+/* A parallelogram spanned by the edges `u` and `v` from `corner`, facing
+ * along u x v. Surface coordinates run from 0 to 1 along each edge.
+ */
+pub struct Quad {
+    corner: Point,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    d: f64,
+    /* n / (n . n), used to find the hit point's edge coordinates */
+    w: Vec3,
+    area: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: Point, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = Vec3::cross_product(&u, &v);
+        let normal = n.unit();
+
+        Quad {
+            corner,
+            u,
+            v,
+            normal,
+            d: Vec3::dot(&normal, &corner),
+            w: n / Vec3::dot(&n, &n),
+            area: n.length(),
+            material,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let denominator = Vec3::dot(&self.normal, r.get_direction());
+        if denominator.abs() < 1e-8 {
+            return (false, None);
+        }
+
+        let root = (self.d - Vec3::dot(&self.normal, r.get_origin())) / denominator;
+        if root < t.0 || root > t.1 {
+            return (false, None);
+        }
+
+        let point = r.at(root);
+        let planar = point - self.corner;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross_product(&planar, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross_product(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return (false, None);
+        }
+
+        let mut rec = HitRecord::new(
+            point,
+            self.normal,
+            root,
+            (alpha, beta),
+            false,
+            self.material.as_ref(),
+        );
+        rec.set_face_normal(r, self.normal);
+
+        (true, Some(rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut bounds = Aabb::new(self.corner, self.corner + self.u + self.v);
+        bounds.grow(&(self.corner + self.u));
+        bounds.grow(&(self.corner + self.v));
+
+        Some(bounds.padded(1e-4))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        match self.hit(&ray, (0.001, f64::INFINITY)) {
+            (true, Some(rec)) => planar_pdf(direction, rec.t, &self.normal, self.area),
+            _ => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        let point = self.corner + u.0 * self.u + u.1 * self.v;
+        point - *origin
+    }
+}
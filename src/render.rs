@@ -0,0 +1,324 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use crate::medium::Medium;
+use crate::objects::{Hittable, HittableList};
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::utility::{self, Rng};
+use crate::vec3::Color;
+
+/* What rays escaping the scene see */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Background {
+    /* A vertical gradient from white to light blue */
+    Sky,
+    Color(Color),
+}
+
+impl Background {
+    fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let unit_direction = r.get_direction().unit();
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Color(c) => *c,
+        }
+    }
+}
+
+/* Everything rays can meet on their way through the scene */
+pub struct World<'a> {
+    pub objects: &'a dyn Hittable,
+    /* Volumes tracked along every ray rather than intersected */
+    pub media: &'a [Box<dyn Medium>],
+    /* Emitting objects, also in `objects`, sampled for direct lighting */
+    pub lights: &'a HittableList,
+    pub background: Background,
+}
+
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u32,
+    pub max_depth: i16,
+    /* Bounces after which paths may be ended by Russian roulette */
+    pub roulette_depth: i16,
+    pub sampler: SamplerKind,
+    pub seed: u64,
+    pub threads: usize,
+    pub tile_size: usize,
+}
+
+/* A rectangle of pixels, rows counted from the top of the image. */
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+/* The light arriving along `r` from whatever it meets first, dimmed by the
+ * media it passes through.
+ */
+fn incoming_light(r: &Ray, world: &World, rng: &mut Rng) -> Color {
+    let (_, record) = world.objects.hit(r, (0.001, f64::INFINITY));
+    let (t_max, light) = match record {
+        Some(record) => (record.t, record.material.emitted(&record)),
+        None => (f64::INFINITY, world.background.color(r)),
+    };
+
+    let transmittance: f64 = world
+        .media
+        .iter()
+        .map(|medium| medium.transmittance(r, (0.001, t_max), rng))
+        .product();
+
+    transmittance * light
+}
+
+/* The weight the power heuristic gives a sample taken with density `pdf`
+ * when another strategy could have picked it with density `other`.
+ */
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    a / (a + b)
+}
+
+/* Follows a path starting with the camera ray `ray` through the scene,
+ * adding up the light it picks up. `throughput` is how much of the light
+ * found at the current vertex reaches the camera.
+ *
+ * Light reaches each vertex in two ways: by a shadow ray sent towards a
+ * random point on one of the lights (next event estimation), which finds
+ * small lights far more often, and by the scattered ray happening to hit a
+ * light, which does better on glossy surfaces facing large lights. Both
+ * are counted with multiple importance sampling, weighing each by the power
+ * heuristic on the densities with which the two strategies pick the same
+ * direction, so each contributes where it is the less noisy one. Light
+ * reached through mirrors and glass, which cannot be sampled, is counted in
+ * full.
+ *
+ * Every bounce takes the same dimensions of the sampler, whether or not it
+ * uses them all, so that each dimension means the same thing in all the
+ * samples of a pixel and the sampler can spread them well. Media take their
+ * random numbers from `rng` instead, as tracking through them needs an
+ * unknown number.
+ *
+ * After `roulette_depth` bounces paths are randomly cut short with a
+ * probability that grows as their throughput falls, and survivors are
+ * weighted up to make up for the ones lost, which ends dim paths early
+ * without darkening the image. `max_depth` only stops paths that never get
+ * unlucky, such as those trapped between mirrors.
+ */
+fn ray_color(
+    mut ray: Ray,
+    world: &World,
+    settings: &RenderSettings,
+    sampler: &mut dyn Sampler,
+    rng: &mut Rng,
+) -> Color {
+    let mut radiance = Color::new(0.0, 0.0, 0.0);
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    // the density the last vertex scattered `ray` with, if lights could
+    // have been sampled in its direction too
+    let mut scatter_pdf: Option<f64> = None;
+
+    for depth in 1..=settings.max_depth {
+        let light_sample = sampler.get_2d();
+        let (lobe_sample, direction_sample) = (sampler.get_1d(), sampler.get_2d());
+        let roulette_sample = sampler.get_1d();
+
+        let (_, mut nearest) = world.objects.hit(&ray, (0.001, f64::INFINITY));
+
+        // A collision inside a medium in front of the nearest surface is
+        // where the ray interacts instead.
+        for medium in world.media {
+            let t_max = nearest.as_ref().map_or(f64::INFINITY, |record| record.t);
+            if let Some(record) = medium.collide(&ray, (0.001, t_max), rng) {
+                nearest = Some(record);
+            }
+        }
+
+        let weight = match scatter_pdf {
+            Some(pdf) => power_heuristic(
+                pdf,
+                world
+                    .lights
+                    .pdf_value(ray.get_origin(), ray.get_direction()),
+            ),
+            None => 1.0,
+        };
+
+        let record = match nearest {
+            Some(record) => record,
+            None => {
+                radiance += weight * throughput * world.background.color(&ray);
+                break;
+            }
+        };
+        radiance += weight * throughput * record.material.emitted(&record);
+
+        let wo = -ray.get_direction().unit();
+
+        if !world.lights.is_empty() {
+            let direction = world.lights.random(&record.point, light_sample);
+            let wi = direction.unit();
+            let f = record.material.eval(&record, &wi, &wo);
+            let light_pdf = world.lights.pdf_value(&record.point, &direction);
+
+            if light_pdf > 0.0 && f != Color::new(0.0, 0.0, 0.0) {
+                let weight = power_heuristic(light_pdf, record.material.pdf(&record, &wi, &wo));
+                // along the unit direction, so that the offset skipped to
+                // avoid hitting the surface itself is the same at any distance
+                let shadow = Ray::new(record.point, wi, ray.get_time());
+                radiance +=
+                    throughput * f * incoming_light(&shadow, world, rng) * (weight / light_pdf);
+            }
+        }
+
+        let scatter = match record
+            .material
+            .sample(&record, &wo, lobe_sample, direction_sample)
+        {
+            Some(scatter) => scatter,
+            None => break,
+        };
+        throughput *= scatter.weight;
+        scatter_pdf = Some(scatter.pdf).filter(|&pdf| !scatter.is_specular() && pdf > 0.0);
+
+        if depth >= settings.roulette_depth {
+            let survival = throughput
+                .x()
+                .max(throughput.y())
+                .max(throughput.z())
+                .min(1.0);
+            if roulette_sample >= survival {
+                break;
+            }
+            throughput /= survival;
+        }
+
+        ray = Ray::new(record.point, scatter.direction, ray.get_time());
+    }
+
+    radiance
+}
+
+fn tiles(width: usize, height: usize, size: usize) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+
+    for y0 in (0..height).step_by(size) {
+        for x0 in (0..width).step_by(size) {
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1: (x0 + size).min(width),
+                y1: (y0 + size).min(height),
+            });
+        }
+    }
+
+    tiles
+}
+
+fn render_tile(
+    camera: &Camera,
+    world: &World,
+    settings: &RenderSettings,
+    tile: &Tile,
+) -> Vec<Color> {
+    let mut colors = Vec::with_capacity((tile.x1 - tile.x0) * (tile.y1 - tile.y0));
+    let scale = 1.0 / settings.samples_per_pixel as f64;
+    let mut sampler = settings
+        .sampler
+        .build(settings.seed, settings.samples_per_pixel);
+
+    for row in tile.y0..tile.y1 {
+        let j = settings.height - 1 - row;
+
+        for i in tile.x0..tile.x1 {
+            // Every sample gets its own sample values and random sequence,
+            // found from the pixel and sample index, so that the image does
+            // not depend on which thread rendered it or in what order.
+            let pixel =
+                utility::mix(settings.seed ^ utility::mix((row * settings.width + i) as u64));
+
+            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+            for sample in 0..settings.samples_per_pixel {
+                sampler.start((i, row), sample);
+                let mut rng = Rng::new(utility::mix(pixel ^ sample as u64));
+
+                let (x, y) = sampler.get_2d();
+                let u = (i as f64 + x) / settings.width as f64;
+                let v = (j as f64 + y) / settings.height as f64;
+                let lens = sampler.get_2d();
+                let ray = camera.get_ray(u, v, lens, sampler.get_1d());
+                pixel_color += ray_color(ray, world, settings, sampler.as_mut(), &mut rng);
+            }
+
+            colors.push(pixel_color * scale);
+        }
+    }
+
+    colors
+}
+
+/* Renders the image in tiles spread across `settings.threads` threads into a
+ * framebuffer holding the average color of every pixel. `progress` is called
+ * on the calling thread with the number of finished tiles and the total after
+ * each tile completes.
+ */
+pub fn render<F: FnMut(usize, usize)>(
+    camera: &Camera,
+    world: &World,
+    settings: &RenderSettings,
+    mut progress: F,
+) -> Framebuffer {
+    let tiles = tiles(settings.width, settings.height, settings.tile_size.max(1));
+    let next = AtomicUsize::new(0);
+    let mut framebuffer = Framebuffer::new(settings.width, settings.height);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..settings.threads.max(1) {
+            let sender = sender.clone();
+            let tiles = &tiles;
+            let next = &next;
+
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= tiles.len() {
+                    break;
+                }
+
+                let colors = render_tile(camera, world, settings, &tiles[index]);
+                if sender.send((index, colors)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (done, (index, colors)) in receiver.iter().enumerate() {
+            let tile = &tiles[index];
+            let mut colors = colors.into_iter();
+
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    framebuffer.set(x, y, colors.next().unwrap());
+                }
+            }
+
+            progress(done + 1, tiles.len());
+        }
+    });
+
+    framebuffer
+}
@@ -0,0 +1,580 @@
+use std::sync::OnceLock;
+
+use crate::utility::{self, Rng};
+
+/* Where the renderer's sample values come from. A sample of a pixel is a
+ * point in many dimensions, handed out in a fixed order: the position
+ * within the pixel, on the lens and in time, then the same few dimensions
+ * for every bounce. Samplers other than the independent one place the
+ * samples of a pixel so that together they cover every dimension more
+ * evenly than independent random numbers would, which makes images
+ * converge faster.
+ */
+pub trait Sampler {
+    /* Moves on to sample `index` of the pixel in column `pixel.0` and row
+     * `pixel.1`, starting over from its first dimension.
+     */
+    fn start(&mut self, pixel: (usize, usize), index: u32);
+
+    /* The next dimension of the current sample, in [0, 1) */
+    fn get_1d(&mut self) -> f64;
+
+    /* The next two dimensions of the current sample, in [0, 1)^2 */
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SamplerKind {
+    /* Independent random numbers */
+    Independent,
+    /* Jittered strata, shuffled separately for every dimension */
+    Stratified,
+    /* The Halton sequence, Owen scrambled for every pixel */
+    Halton,
+    /* The Sobol sequence with hashed Owen scrambling for every pixel */
+    Sobol,
+    /* The same Sobol points in every pixel, shifted by a blue noise mask */
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        match name {
+            "independent" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            "blue-noise" => Some(SamplerKind::BlueNoise),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+            SamplerKind::BlueNoise => "blue-noise",
+        }
+    }
+
+    /* A sampler of this kind for `samples_per_pixel` samples of every
+     * pixel. Samplers keep the position of the current sample, so every
+     * render thread builds its own.
+     */
+    pub fn build(&self, seed: u64, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        let position = Position {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        };
+
+        match self {
+            SamplerKind::Independent => Box::new(Independent {
+                seed,
+                rng: Rng::new(seed),
+            }),
+            SamplerKind::Stratified => Box::new(Stratified {
+                position,
+                samples: samples_per_pixel.max(1),
+            }),
+            SamplerKind::Halton => Box::new(Halton { position }),
+            SamplerKind::Sobol => Box::new(Sobol { position }),
+            SamplerKind::BlueNoise => Box::new(BlueNoise {
+                position,
+                coordinates: (0, 0),
+                mask: blue_noise_mask(),
+            }),
+        }
+    }
+}
+
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, &v| utility::mix(hash ^ v))
+}
+
+/* The top 53 bits of `bits` as a number in [0, 1) */
+fn unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn unit32(bits: u32) -> f64 {
+    bits as f64 / (1u64 << 32) as f64
+}
+
+/* `x + offset` wrapped around into [0, 1) */
+fn shift(x: f64, offset: f64) -> f64 {
+    let shifted = x + offset;
+    if shifted >= 1.0 {
+        shifted - 1.0
+    } else {
+        shifted
+    }
+}
+
+/* The current sample of a sampler working through dimensions in order */
+struct Position {
+    seed: u64,
+    /* A hash of the seed and the pixel, distinct for every pixel */
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl Position {
+    fn start(&mut self, pixel: (usize, usize), index: u32) {
+        self.pixel = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /* Claims the next `count` dimensions, returning the first */
+    fn claim(&mut self, count: u64) -> u64 {
+        let dimension = self.dimension;
+        self.dimension += count;
+        dimension
+    }
+}
+
+struct Independent {
+    seed: u64,
+    rng: Rng,
+}
+
+impl Sampler for Independent {
+    fn start(&mut self, pixel: (usize, usize), index: u32) {
+        self.rng = Rng::new(hash(&[
+            self.seed,
+            pixel.0 as u64,
+            pixel.1 as u64,
+            index as u64,
+        ]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.double()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        self.rng.pair()
+    }
+}
+
+/* Element `i` of a random permutation of 0..`length` chosen by `seed`,
+ * found without storing the permutation by Kensler's hashing and cycle
+ * walking.
+ */
+fn permutation_element(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+
+    i.wrapping_add(seed) % length
+}
+
+/* Splits every dimension into as many strata as there are samples, and
+ * every pair of dimensions into a grid of about as many cells, giving each
+ * sample its own stratum or cell at a random place within it. Which sample
+ * gets which stratum is shuffled separately for every dimension so that
+ * dimensions do not line up with each other.
+ */
+struct Stratified {
+    position: Position,
+    samples: u32,
+}
+
+impl Sampler for Stratified {
+    fn start(&mut self, pixel: (usize, usize), index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let p = &mut self.position;
+        let dimension = p.claim(1);
+        let h = hash(&[p.pixel, dimension, p.index as u64]);
+
+        let permutation = hash(&[p.pixel, dimension]) as u32;
+        let stratum = permutation_element(p.index % self.samples, self.samples, permutation);
+        (stratum as f64 + unit(h)) / self.samples as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let p = &mut self.position;
+        let dimension = p.claim(2);
+        let h = hash(&[p.pixel, dimension, p.index as u64]);
+
+        let columns = (self.samples as f64).sqrt().ceil() as u32;
+        let rows = self.samples.div_ceil(columns);
+        let permutation = hash(&[p.pixel, dimension]) as u32;
+        let cell = permutation_element(p.index % self.samples, columns * rows, permutation);
+
+        (
+            ((cell % columns) as f64 + unit32(h as u32)) / columns as f64,
+            ((cell / columns) as f64 + unit32((h >> 32) as u32)) / rows as f64,
+        )
+    }
+}
+
+/* Bases of the Halton sequence's first dimensions. Higher dimensions, which
+ * only the later bounces of long paths reach, fall back to independent
+ * random numbers.
+ */
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/* `index` written in `base` and mirrored around the radix point, with
+ * every digit put through a random permutation of the digits chosen by
+ * `seed` and the digits before it: Owen scrambling in any base. Unlike a
+ * single random shift this gives every dimension its own order of the
+ * points, so that dimensions with large bases, whose first few points
+ * would otherwise climb slowly through [0, 1) in step with each other,
+ * neither correlate nor bunch up.
+ */
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut reversed = 0;
+    let mut scale = 1.0;
+
+    // The leading zeros of `index` are scrambled into random digits too,
+    // up to the last one a double can still resolve.
+    while scale > f64::EPSILON / 2.0 {
+        let next = index / base;
+        let digit = index - next * base;
+        let permuted = permutation_element(
+            digit as u32,
+            base as u32,
+            utility::mix(seed ^ reversed) as u32,
+        );
+        reversed = reversed * base + permuted as u64;
+        scale *= inverse_base;
+        index = next;
+    }
+
+    (reversed as f64 * scale).min(1.0 - f64::EPSILON / 2.0)
+}
+
+/* Dimension d of sample i is the radical inverse of i in the d-th prime,
+ * Owen scrambled differently in every pixel and dimension, which keeps the
+ * points evenly spread while stopping neighbouring pixels from repeating
+ * the same pattern.
+ */
+struct Halton {
+    position: Position,
+}
+
+impl Halton {
+    fn dimension(&self, dimension: u64) -> f64 {
+        let p = &self.position;
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                scrambled_radical_inverse(base, p.index as u64, hash(&[p.pixel, dimension]))
+            }
+            None => unit(hash(&[p.pixel, dimension, p.index as u64])),
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start(&mut self, pixel: (usize, usize), index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.position.claim(1);
+        self.dimension(dimension)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.position.claim(2);
+        (self.dimension(dimension), self.dimension(dimension + 1))
+    }
+}
+
+/* The first two dimensions of the Sobol sequence: the van der Corput
+ * sequence, and the one generated by the Pascal matrix.
+ */
+fn sobol(index: u32) -> (u32, u32) {
+    let mut y = 0;
+    let mut direction = 1u32 << 31;
+    let mut i = index;
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= direction;
+        }
+        i >>= 1;
+        direction ^= direction >> 1;
+    }
+
+    (index.reverse_bits(), y)
+}
+
+/* Owen scrambling, a random permutation of every level of the binary
+ * subdivision of [0, 1), by Burley's hash of the bits reversed.
+ */
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/* Point `index` of the Owen scrambled two dimensional Sobol sequence chosen
+ * by `seed`. The index is scrambled too, which shuffles the points so that
+ * different pairs of dimensions taken from the same two Sobol dimensions
+ * do not correlate (Burley's padding).
+ */
+fn scrambled_sobol(index: u32, seed: u64) -> (f64, f64) {
+    let (x, y) = sobol(owen_scramble(index, seed as u32));
+    (
+        unit32(owen_scramble(x, hash(&[seed, 1]) as u32)),
+        unit32(owen_scramble(y, hash(&[seed, 2]) as u32)),
+    )
+}
+
+/* Hashed Owen scrambled Sobol points, scrambled differently in every pixel
+ * and for every dimension or pair of dimensions. Sample counts that are
+ * powers of two cover each pair of dimensions best.
+ */
+struct Sobol {
+    position: Position,
+}
+
+impl Sampler for Sobol {
+    fn start(&mut self, pixel: (usize, usize), index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let p = &mut self.position;
+        let dimension = p.claim(1);
+        scrambled_sobol(p.index, hash(&[p.pixel, dimension])).0
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let p = &mut self.position;
+        let dimension = p.claim(2);
+        scrambled_sobol(p.index, hash(&[p.pixel, dimension]))
+    }
+}
+
+/* Every pixel takes the same scrambled Sobol points, shifted by the value
+ * of a blue noise mask at the pixel. Neighbouring pixels then get very
+ * different shifts, which leaves the error of an image as fine grained,
+ * high frequency noise that looks smoother at low sample counts than the
+ * clumps independent pixels give. Each dimension reads the mask at its own
+ * offset.
+ */
+struct BlueNoise {
+    position: Position,
+    coordinates: (usize, usize),
+    mask: &'static [f64],
+}
+
+impl BlueNoise {
+    fn offset(&self, dimension: u64) -> f64 {
+        let h = hash(&[self.position.seed, dimension, 3]);
+        let x = (self.coordinates.0 + (h as usize % MASK_SIZE)) % MASK_SIZE;
+        let y = (self.coordinates.1 + ((h >> 32) as usize % MASK_SIZE)) % MASK_SIZE;
+        self.mask[y * MASK_SIZE + x]
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn start(&mut self, pixel: (usize, usize), index: u32) {
+        self.position.start(pixel, index);
+        self.coordinates = pixel;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.position.claim(1);
+        let seed = hash(&[self.position.seed, dimension]);
+        let (x, _) = scrambled_sobol(self.position.index, seed);
+
+        shift(x, self.offset(dimension))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.position.claim(2);
+        let seed = hash(&[self.position.seed, dimension]);
+        let (x, y) = scrambled_sobol(self.position.index, seed);
+
+        (
+            shift(x, self.offset(dimension)),
+            shift(y, self.offset(dimension + 1)),
+        )
+    }
+}
+
+const MASK_SIZE: usize = 64;
+
+/* A tileable blue noise mask of MASK_SIZE^2 values evenly spread over
+ * [0, 1), made once on first use.
+ */
+fn blue_noise_mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(MASK_SIZE, 1.9))
+}
+
+/* Ulichney's void and cluster method. Every pixel's energy is the sum of a
+ * Gaussian of its wrapped distance to each pixel set in a binary pattern.
+ * Starting from a random pattern with its tightest clusters moved into its
+ * largest voids, pixels are ranked by taking the pattern's ones away
+ * tightest cluster first and then filling its zeros largest void first;
+ * the rank is the mask value.
+ */
+fn void_and_cluster(size: usize, sigma: f64) -> Vec<f64> {
+    let n = size * size;
+    let kernel: Vec<f64> = (0..n)
+        .map(|k| {
+            let (dx, dy) = (k % size, k / size);
+            let (dx, dy) = (dx.min(size - dx), dy.min(size - dy));
+            (-((dx * dx + dy * dy) as f64) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+
+    let splat = |energy: &mut [f64], p: usize, sign: f64| {
+        let (px, py) = (p % size, p / size);
+        for (q, e) in energy.iter_mut().enumerate() {
+            let dx = (q % size + size - px) % size;
+            let dy = (q / size + size - py) % size;
+            *e += sign * kernel[dy * size + dx];
+        }
+    };
+    // the tightest cluster among pixels set to `set`, or the largest void
+    let extreme = |energy: &[f64], pattern: &[bool], set: bool| {
+        let candidates = (0..n).filter(|&p| pattern[p] == set);
+        if set {
+            candidates.max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+        } else {
+            candidates.min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+        }
+        .unwrap()
+    };
+
+    let mut rng = Rng::new(0);
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0; n];
+    let initial = n / 10;
+    let mut count = 0;
+    while count < initial {
+        let p = ((rng.double() * n as f64) as usize).min(n - 1);
+        if !pattern[p] {
+            pattern[p] = true;
+            splat(&mut energy, p, 1.0);
+            count += 1;
+        }
+    }
+
+    loop {
+        let cluster = extreme(&energy, &pattern, true);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+
+        let void = extreme(&energy, &pattern, false);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; n];
+
+    let (mut ones, mut ones_energy) = (pattern.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = extreme(&ones_energy, &ones, true);
+        ones[cluster] = false;
+        splat(&mut ones_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    for r in initial..n {
+        let void = extreme(&energy, &pattern, false);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+
+    rank.iter().map(|&r| (r as f64 + 0.5) / n as f64).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* The root mean square error, over many pixels, of `samples` sample
+     * estimates of the integral of x * y over dimensions 12 and 13, which is
+     * 1/4.
+     */
+    fn error(kind: SamplerKind, samples: u32) -> f64 {
+        let mut sampler = kind.build(7, samples);
+        let pixels = 1024;
+        let mut squared = 0.0;
+
+        for pixel in 0..pixels {
+            let mut sum = 0.0;
+            for index in 0..samples {
+                sampler.start((pixel, 0), index);
+                for _ in 0..12 {
+                    sampler.get_1d();
+                }
+                let (x, y) = sampler.get_2d();
+                sum += x * y;
+            }
+            squared += (sum / samples as f64 - 0.25).powi(2);
+        }
+
+        (squared / pixels as f64).sqrt()
+    }
+
+    #[test]
+    fn halton_beats_independent_in_high_dimensions() {
+        for samples in [16, 64] {
+            let halton = error(SamplerKind::Halton, samples);
+            let independent = error(SamplerKind::Independent, samples);
+            assert!(
+                halton < independent,
+                "{} samples: halton {} independent {}",
+                samples,
+                halton,
+                independent
+            );
+        }
+    }
+}
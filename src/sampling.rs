@@ -0,0 +1,120 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::vec3::Vec3;
+
+/* Mappings from points of the unit square, uniform in [0, 1)^2, to
+ * directions and points distributed as the renderer needs them. They are
+ * closed form rather than rejection sampled, so every sample point maps to
+ * exactly one result and well spread sample points stay well spread.
+ * Directions are given in a local frame with z up; an `Onb` turns them
+ * into world space.
+ */
+
+/* An orthonormal basis: the unit vectors `u` and `v` spanning the plane
+ * perpendicular to `w`, oriented so that u x v = w.
+ */
+#[derive(Debug, Copy, Clone)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /* A basis around the unit vector `w` */
+    pub fn new(w: &Vec3) -> Onb {
+        // any axis not parallel to w gives a tangent
+        let helper = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let u = Vec3::cross_product(&helper, w).unit();
+        let v = Vec3::cross_product(w, &u);
+
+        Onb { u, v, w: *w }
+    }
+
+    pub fn u(&self) -> &Vec3 {
+        &self.u
+    }
+
+    pub fn v(&self) -> &Vec3 {
+        &self.v
+    }
+
+    /* The world space vector with coordinates `a` in this basis */
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
+
+/* A point of the unit disk. Shirley and Chiu's concentric mapping takes
+ * squares around the center to rings, so neighbouring sample points stay
+ * neighbours and areas keep their proportions.
+ */
+pub fn concentric_disk(sample: (f64, f64)) -> (f64, f64) {
+    let a = 2.0 * sample.0 - 1.0;
+    let b = 2.0 * sample.1 - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+
+    (r * theta.cos(), r * theta.sin())
+}
+
+/* A unit vector, uniform over all directions */
+pub fn uniform_sphere(sample: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * sample.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * sample.1;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f64 {
+    1.0 / (4.0 * PI)
+}
+
+/* A point uniform in the ball of unit radius, which takes a third sample
+ * for the distance from the center.
+ */
+pub fn uniform_ball(sample: (f64, f64, f64)) -> Vec3 {
+    sample.2.cbrt() * uniform_sphere((sample.0, sample.1))
+}
+
+/* A unit vector in the upper hemisphere, z >= 0, with a density
+ * proportional to the cosine of its angle to the z axis: a point of the unit
+ * disk lifted up onto the hemisphere.
+ */
+pub fn cosine_hemisphere(sample: (f64, f64)) -> Vec3 {
+    let (x, y) = concentric_disk(sample);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+    Vec3::new(x, y, z)
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f64) -> f64 {
+    cos_theta.max(0.0) / PI
+}
+
+/* A unit vector uniform over the directions within the angle whose cosine
+ * is `cos_theta_max` of the z axis.
+ */
+pub fn uniform_cone(sample: (f64, f64), cos_theta_max: f64) -> Vec3 {
+    let cos_theta = 1.0 + sample.0 * (cos_theta_max - 1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * sample.1;
+
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: f64) -> f64 {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
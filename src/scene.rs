@@ -0,0 +1,1176 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::camera::Camera;
+use crate::cuboid::Cuboid;
+use crate::disk::Disk;
+use crate::medium::{
+    ConstantMedium, DensityField, HeterogeneousMedium, Medium, NoiseField, VoxelGrid,
+};
+use crate::mesh::{Triangle, TriangleMesh};
+use crate::noise::{Perlin, Smoothing, Worley};
+use crate::obj;
+use crate::objects::{
+    Dielectric, DiffuseLight, Hittable, HittableList, Lambertian, Material, Metal, Volumetric,
+};
+use crate::phase::{HenyeyGreenstein, Isotropic};
+use crate::ply;
+use crate::rect::{AxisRect, Plane, Quad};
+use crate::render::Background;
+use crate::sampler::SamplerKind;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{
+    CheckerTexture, ColorSpace, Filter, ImageTexture, NoisePattern, NoiseTexture, SolidColor,
+    Texture, UvCheckerTexture, WorleyTexture, Wrap,
+};
+use crate::transform::{Animated, Placement, Transformed};
+use crate::vec3::{Color, Point, Vec3};
+
+/* A scene file is a sequence of directives, one per line. Blank lines and
+ * anything after a '#' are ignored. Each directive is a keyword followed by
+ * positional arguments and then `key value...` pairs, in any order but each
+ * key at most once:
+ *
+ *   image width 1200 aspect 1.5 samples 500 depth 50 roulette 3 sampler sobol
+ *   camera from 13 2 3 at 0 0 0 up 0 1 0 fov 20 aperture 0.1 focus 10 shutter 0 1
+ *   background color 0 0 0
+ *   texture white solid color 0.9 0.9 0.9
+ *   texture green solid color 0.2 0.3 0.1
+ *   texture checks checker even white odd green size 0.5
+ *   texture tiles uv-checker even white odd green columns 16 rows 8
+ *   texture earth image earthmap.png filter bilinear wrap repeat colorspace srgb
+ *   texture veins marble low green high white scale 4 octaves 7 seed 1
+ *   texture cobbles worley low green high white scale 2
+ *   material ground lambertian texture checks
+ *   material planet lambertian texture earth
+ *   material steel metal albedo 0.7 0.6 0.5 fuzz 0.0
+ *   material glass dielectric ior 1.5
+ *   material lamp light emit 1 0.9 0.8 intensity 4
+ *   material smoke isotropic albedo 0.2 0.2 0.2
+ *   material haze henyey-greenstein albedo 0.9 0.9 0.9 g 0.6
+ *   sphere center 0 -1000 0 radius 1000 material ground
+ *   sphere center 2 1 0 radius 0.2 material steel end-center 2 1.5 0
+ *   rect xz min -5 -5 max 5 5 offset 0 material ground
+ *   quad corner 0 0 0 u 1 0 0 v 0 1 0 material steel
+ *   disk center 0 3 0 normal 0 -1 0 radius 0.5 material lamp
+ *   box min 0 0 0 max 1 2 1 material steel
+ *   triangle a 0 0 0 b 1 0 0 c 0 1 0 material steel
+ *   mesh teapot.obj
+ *   mesh scan.ply material steel
+ *   box min 0 0 0 max 1 1 1 material steel scale 1 2 1 rotate 0 1 0 15 translate 3 0 0
+ *   sphere center 0 1 0 radius 1 material smoke density 0.5
+ *   medium grid plume.raw resolution 32 64 32 min -1 0 -1 max 1 4 1 density 5 material haze
+ *   medium noise min -4 2 -4 max 4 3 4 density 2 frequency 0.8 octaves 5 seed 3 material haze
+ *
+ * The image's `sampler` decides where sample values come from: independent
+ * random numbers, or stratified, halton, sobol (default) or blue-noise
+ * points spreading the samples of each pixel evenly.
+ *
+ * Textures and materials must be declared before they are used. Image
+ * textures read PNG, PPM and Radiance HDR files; relative paths are resolved
+ * against the scene file's directory. Their `filter` is nearest or bilinear
+ * (default), `wrap` repeat (default), clamp or mirror, and `colorspace` srgb
+ * (default) or linear for 8 and 16 bit images.
+ *
+ * Procedural textures blend from their `low` texture (default black) to the
+ * `high` one (white) by a pattern of `scale` times the position (default 1):
+ * `noise`, `turbulence`, `marble` and `wood` sum `octaves` (7) layers of
+ * Perlin noise with `smoothing` hermite (default) or trilinear, and `worley`
+ * is the distance to the nearest point of a cellular pattern. The same
+ * `seed` (0) always gives the same pattern. The background seen by rays
+ * leaving the scene is either `sky`, the default gradient, or `color r g b`.
+ *
+ * Rectangles lie in the xy, xz or yz plane at `offset` along the remaining
+ * axis and face its positive direction; quads face along u x v. Meshes are
+ * Wavefront OBJ files using the materials from their MTL libraries or PLY
+ * files, whose vertex colors tint Lambertian and metal albedos, unless a
+ * `material` is given. Any object can be given `scale x y z`, `rotate x y z
+ * degrees` and `translate x y z`, applied in that order; placing the same
+ * mesh file several times loads it once. Objects move from their placement
+ * at time 0 to the one given by `end-scale`, `end-rotate`, `end-translate`
+ * (or a sphere's `end-center`) at time 1, and are blurred over the camera's
+ * `shutter` interval, which is closed (0 0) by default. Giving an object a
+ * `density` fills it with a uniform medium instead, usually with an
+ * isotropic or henyey-greenstein material, whose `g` between -1 and 1 makes
+ * it scatter mostly back or forward.
+ *
+ * Media with varying density fill the box from `min` to `max`. A `grid`
+ * reads its voxels from a raw file of little endian 32 bit floats, x
+ * varying fastest and z slowest; `noise` is fractal Perlin noise of the
+ * given `frequency` (default 1), `octaves` (4) and `seed` (0), shifted by
+ * `coverage` (0) between -1 for empty and 1 for nearly solid. Either is
+ * scaled by `density`.
+ *
+ * Spheres, rectangles, quads, disks, boxes and triangles with a light
+ * material are sampled directly when lighting diffuse surfaces, which
+ * removes most of the noise of small lights. Meshes and moved or animated
+ * objects still light the scene, but only when rays happen to hit them.
+ */
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> SceneError {
+        SceneError::Io(e)
+    }
+}
+
+pub struct ImageSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u32,
+    /* A cap on the bounces of a path */
+    pub max_depth: i16,
+    /* Bounces after which paths may end by Russian roulette */
+    pub roulette_depth: i16,
+    pub sampler: SamplerKind,
+}
+
+impl ImageSettings {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+}
+
+impl Default for ImageSettings {
+    fn default() -> ImageSettings {
+        ImageSettings {
+            width: 1200,
+            height: 800,
+            samples_per_pixel: 500,
+            max_depth: 50,
+            roulette_depth: 3,
+            sampler: SamplerKind::Sobol,
+        }
+    }
+}
+
+pub struct CameraSettings {
+    pub look_from: Point,
+    pub look_at: Point,
+    pub vup: Vec3,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub focus_distance: Option<f64>,
+    /* Scene time interval the shutter is open for; objects move between
+     * times 0 and 1.
+     */
+    pub shutter: (f64, f64),
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        let focus_distance = self
+            .focus_distance
+            .unwrap_or_else(|| (self.look_from - self.look_at).length());
+
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            self.vup,
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            focus_distance,
+        )
+        .with_shutter(self.shutter)
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            look_from: Point::new(0.0, 0.0, 0.0),
+            look_at: Point::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aperture: 0.0,
+            focus_distance: None,
+            shutter: (0.0, 0.0),
+        }
+    }
+}
+
+pub struct Scene {
+    pub image: ImageSettings,
+    pub camera: CameraSettings,
+    pub background: Background,
+    pub world: HittableList,
+    pub media: Vec<Box<dyn Medium>>,
+    /* The emitting objects of `world` that direct lighting samples */
+    pub lights: HittableList,
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        Scene::parse(&source, directory)
+    }
+
+    pub fn parse(source: &str, directory: &Path) -> Result<Scene, SceneError> {
+        let mut parser = Parser::new(directory);
+
+        for (index, raw) in source.lines().enumerate() {
+            let content = match raw.find('#') {
+                Some(i) => &raw[..i],
+                None => raw,
+            };
+
+            let mut tokens = content.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(k) => k,
+                None => continue,
+            };
+
+            let mut directive = Directive::new(index + 1, keyword, tokens.collect());
+            parser.directive(keyword, &mut directive)?;
+            directive.finish()?;
+        }
+
+        Ok(parser.scene)
+    }
+}
+
+struct Parser {
+    scene: Scene,
+    directory: PathBuf,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    meshes: HashMap<(PathBuf, Option<String>), Arc<dyn Hittable>>,
+}
+
+impl Parser {
+    fn new(directory: &Path) -> Parser {
+        Parser {
+            scene: Scene {
+                image: ImageSettings::default(),
+                camera: CameraSettings::default(),
+                background: Background::Sky,
+                world: HittableList::new(),
+                media: Vec::new(),
+                lights: HittableList::new(),
+            },
+            directory: directory.to_path_buf(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            meshes: HashMap::new(),
+        }
+    }
+
+    fn directive(&mut self, keyword: &str, d: &mut Directive) -> Result<(), SceneError> {
+        match keyword {
+            "image" => self.image(d),
+            "camera" => self.camera(d),
+            "background" => self.background(d),
+            "texture" => self.texture(d),
+            "material" => self.material(d),
+            "sphere" => self.sphere(d),
+            "rect" => self.rect(d),
+            "quad" => self.quad(d),
+            "disk" => self.disk(d),
+            "box" => self.cuboid(d),
+            "triangle" => self.triangle(d),
+            "mesh" => self.mesh(d),
+            "medium" => self.medium(d),
+            _ => Err(d.error(format!("unknown directive `{}`", keyword))),
+        }
+    }
+
+    fn image(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let image = &mut self.scene.image;
+        let previous_aspect = image.aspect_ratio();
+        let width = d.uint("width")?.unwrap_or(image.width);
+        let height = d.uint("height")?;
+        let aspect = d.float("aspect")?;
+
+        if width == 0 {
+            return Err(d.error("image width must be positive"));
+        }
+
+        image.width = width;
+        image.height = match (height, aspect) {
+            (Some(_), Some(_)) => {
+                return Err(d.error("specify either `height` or `aspect`, not both"));
+            }
+            (Some(h), None) => h,
+            (None, Some(a)) if a > 0.0 => (width as f64 / a) as usize,
+            (None, Some(_)) => return Err(d.error("aspect ratio must be positive")),
+            (None, None) => (width as f64 / previous_aspect) as usize,
+        };
+        if image.height == 0 {
+            return Err(d.error("image height must be positive"));
+        }
+
+        if let Some(samples) = d.uint("samples")? {
+            if samples == 0 {
+                return Err(d.error("samples must be positive"));
+            }
+            image.samples_per_pixel = samples as u32;
+        }
+        if let Some(depth) = d.uint("depth")? {
+            if depth == 0 || depth > i16::MAX as usize {
+                return Err(d.error(format!("depth must be between 1 and {}", i16::MAX)));
+            }
+            image.max_depth = depth as i16;
+        }
+        if let Some(depth) = d.uint("roulette")? {
+            if depth == 0 || depth > i16::MAX as usize {
+                return Err(d.error(format!("roulette must be between 1 and {}", i16::MAX)));
+            }
+            image.roulette_depth = depth as i16;
+        }
+        if let Some(name) = d.word("sampler")? {
+            image.sampler = SamplerKind::from_name(name)
+                .ok_or_else(|| d.error(format!("unknown sampler `{}`", name)))?;
+        }
+
+        Ok(())
+    }
+
+    fn camera(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let camera = &mut self.scene.camera;
+
+        if let Some(from) = d.vec3("from")? {
+            camera.look_from = from;
+        }
+        if let Some(at) = d.vec3("at")? {
+            camera.look_at = at;
+        }
+        if let Some(up) = d.vec3("up")? {
+            camera.vup = up;
+        }
+        if let Some(fov) = d.float("fov")? {
+            if fov <= 0.0 || fov >= 180.0 {
+                return Err(d.error("fov must be between 0 and 180 degrees"));
+            }
+            camera.vfov = fov;
+        }
+        if let Some(aperture) = d.float("aperture")? {
+            if aperture < 0.0 {
+                return Err(d.error("aperture must not be negative"));
+            }
+            camera.aperture = aperture;
+        }
+        if let Some(focus) = d.float("focus")? {
+            if focus <= 0.0 {
+                return Err(d.error("focus distance must be positive"));
+            }
+            camera.focus_distance = Some(focus);
+        }
+        if let Some((open, close)) = d.pair("shutter")? {
+            if open > close {
+                return Err(d.error("shutter must not close before it opens"));
+            }
+            camera.shutter = (open, close);
+        }
+
+        if (camera.look_from - camera.look_at).near_zero() {
+            return Err(d.error("camera `from` and `at` must differ"));
+        }
+
+        Ok(())
+    }
+
+    fn background(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        if d.peek() == Some("color") {
+            let c = d.required_vec3("color")?;
+            if c.x() < 0.0 || c.y() < 0.0 || c.z() < 0.0 {
+                return Err(d.error("background color must not be negative"));
+            }
+            self.scene.background = Background::Color(c);
+            return Ok(());
+        }
+
+        self.scene.background = match d.positional("background")? {
+            "sky" => Background::Sky,
+            other => return Err(d.error(format!("unknown background `{}`", other))),
+        };
+        Ok(())
+    }
+
+    fn texture(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let name = d.positional("texture name")?;
+        let kind = d.positional("texture type")?;
+
+        if self.textures.contains_key(name) {
+            return Err(d.error(format!("texture `{}` is already defined", name)));
+        }
+
+        let texture: Arc<dyn Texture> = match kind {
+            "solid" => Arc::new(SolidColor::new(d.required_vec3("color")?)),
+            "checker" => {
+                let even = self.lookup_texture(d, "even")?;
+                let odd = self.lookup_texture(d, "odd")?;
+                let size = d.float("size")?.unwrap_or(1.0);
+                if size <= 0.0 {
+                    return Err(d.error("size must be positive"));
+                }
+                Arc::new(CheckerTexture::new(even, odd, size))
+            }
+            "uv-checker" => {
+                let even = self.lookup_texture(d, "even")?;
+                let odd = self.lookup_texture(d, "odd")?;
+                let columns = d.float("columns")?.unwrap_or(8.0);
+                let rows = d.float("rows")?.unwrap_or(8.0);
+                if columns <= 0.0 || rows <= 0.0 {
+                    return Err(d.error("columns and rows must be positive"));
+                }
+                Arc::new(UvCheckerTexture::new(even, odd, columns, rows))
+            }
+            "noise" | "turbulence" | "marble" | "wood" | "worley" => {
+                let low = match d.word("low")? {
+                    Some(name) => self.texture_named(d, name)?,
+                    None => Arc::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))),
+                };
+                let high = match d.word("high")? {
+                    Some(name) => self.texture_named(d, name)?,
+                    None => Arc::new(SolidColor::new(Color::new(1.0, 1.0, 1.0))),
+                };
+                let scale = d.float("scale")?.unwrap_or(1.0);
+                let seed = d.uint("seed")?.unwrap_or(0) as u64;
+                if scale <= 0.0 {
+                    return Err(d.error("scale must be positive"));
+                }
+
+                if kind == "worley" {
+                    Arc::new(WorleyTexture::new(Worley::new(seed), scale, low, high))
+                } else {
+                    let pattern = match kind {
+                        "noise" => NoisePattern::Noise,
+                        "turbulence" => NoisePattern::Turbulence,
+                        "marble" => NoisePattern::Marble,
+                        _ => NoisePattern::Wood,
+                    };
+                    let smoothing = match d.word("smoothing")?.unwrap_or("hermite") {
+                        "hermite" => Smoothing::Hermite,
+                        "trilinear" => Smoothing::Trilinear,
+                        other => return Err(d.error(format!("unknown smoothing `{}`", other))),
+                    };
+                    let octaves = d.uint("octaves")?.unwrap_or(7);
+                    if octaves == 0 {
+                        return Err(d.error("octaves must be positive"));
+                    }
+                    Arc::new(NoiseTexture::new(
+                        Perlin::new(seed, smoothing),
+                        pattern,
+                        scale,
+                        octaves as u32,
+                        low,
+                        high,
+                    ))
+                }
+            }
+            "image" => {
+                let file = d.positional("image path")?;
+                let path = self.directory.join(file);
+
+                let color_space = match d.word("colorspace")?.unwrap_or("srgb") {
+                    "srgb" => ColorSpace::Srgb,
+                    "linear" => ColorSpace::Linear,
+                    other => return Err(d.error(format!("unknown color space `{}`", other))),
+                };
+                let filter = match d.word("filter")?.unwrap_or("bilinear") {
+                    "nearest" => Filter::Nearest,
+                    "bilinear" => Filter::Bilinear,
+                    other => return Err(d.error(format!("unknown filter `{}`", other))),
+                };
+                let wrap = match d.word("wrap")?.unwrap_or("repeat") {
+                    "repeat" => Wrap::Repeat,
+                    "clamp" => Wrap::Clamp,
+                    "mirror" => Wrap::Mirror,
+                    other => return Err(d.error(format!("unknown wrap mode `{}`", other))),
+                };
+
+                match ImageTexture::load(&path, color_space, filter, wrap) {
+                    Ok(image) => Arc::new(image),
+                    Err(e) => return Err(d.error(format!("{}: {}", path.display(), e))),
+                }
+            }
+            _ => return Err(d.error(format!("unknown texture type `{}`", kind))),
+        };
+
+        self.textures.insert(name.to_string(), texture);
+        Ok(())
+    }
+
+    /* A material's color, given either as `<key> r g b` or `texture name` */
+    fn color(&self, d: &mut Directive, key: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        let color = d.vec3(key)?;
+        let texture = match d.word("texture")? {
+            Some(name) => Some(self.texture_named(d, name)?),
+            None => None,
+        };
+
+        match (color, texture) {
+            (Some(_), Some(_)) => {
+                Err(d.error(format!("specify either `{}` or `texture`, not both", key)))
+            }
+            (Some(c), None) => Ok(Arc::new(SolidColor::new(c))),
+            (None, Some(t)) => Ok(t),
+            (None, None) => Err(d.error(format!("missing `{}` or `texture`", key))),
+        }
+    }
+
+    fn lookup_texture(&self, d: &mut Directive, key: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        let name = d.required_word(key)?;
+        self.texture_named(d, name)
+    }
+
+    fn texture_named(&self, d: &Directive, name: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        match self.textures.get(name) {
+            Some(t) => Ok(t.clone()),
+            None => Err(d.error(format!("undefined texture `{}`", name))),
+        }
+    }
+
+    fn material(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let name = d.positional("material name")?;
+        let kind = d.positional("material type")?;
+
+        if self.materials.contains_key(name) {
+            return Err(d.error(format!("material `{}` is already defined", name)));
+        }
+
+        let material: Arc<dyn Material> = match kind {
+            "lambertian" => Arc::new(Lambertian::with_texture(self.color(d, "albedo")?)),
+            "metal" => {
+                let albedo = self.color(d, "albedo")?;
+                let fuzz = d.float("fuzz")?.unwrap_or(0.0);
+                if fuzz < 0.0 {
+                    return Err(d.error("fuzz must not be negative"));
+                }
+                Arc::new(Metal::with_texture(albedo, fuzz))
+            }
+            "light" => {
+                let emit = self.color(d, "emit")?;
+                let intensity = d.float("intensity")?.unwrap_or(1.0);
+                if intensity < 0.0 {
+                    return Err(d.error("intensity must not be negative"));
+                }
+                Arc::new(DiffuseLight::with_texture(emit, intensity))
+            }
+            "isotropic" => Arc::new(Volumetric::with_texture(
+                self.color(d, "albedo")?,
+                Arc::new(Isotropic),
+            )),
+            "henyey-greenstein" => {
+                let albedo = self.color(d, "albedo")?;
+                let g = d.required_float("g")?;
+                if g <= -1.0 || g >= 1.0 {
+                    return Err(d.error("g must be between -1 and 1"));
+                }
+                Arc::new(Volumetric::with_texture(
+                    albedo,
+                    Arc::new(HenyeyGreenstein::new(g)),
+                ))
+            }
+            "dielectric" => {
+                let ir = d.required_float("ior")?;
+                if ir <= 0.0 {
+                    return Err(d.error("ior must be positive"));
+                }
+                Arc::new(Dielectric::new(ir))
+            }
+            _ => return Err(d.error(format!("unknown material type `{}`", kind))),
+        };
+
+        self.materials.insert(name.to_string(), material);
+        Ok(())
+    }
+
+    fn sphere(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let center = d.required_vec3("center")?;
+        let radius = d.required_float("radius")?;
+        let material = self.lookup_material(d)?;
+
+        if radius <= 0.0 {
+            return Err(d.error("radius must be positive"));
+        }
+
+        match d.vec3("end-center")? {
+            Some(end) => self.place(
+                d,
+                Arc::new(MovingSphere::new(center, end, radius, material.clone())),
+                Some(material),
+                false,
+            ),
+            None => self.place(
+                d,
+                Arc::new(Sphere::new(center, radius, material.clone())),
+                Some(material),
+                true,
+            ),
+        }
+    }
+
+    fn rect(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let plane = match d.positional("plane")? {
+            "xy" => Plane::Xy,
+            "xz" => Plane::Xz,
+            "yz" => Plane::Yz,
+            other => return Err(d.error(format!("unknown plane `{}`", other))),
+        };
+        let min = d.required_pair("min")?;
+        let max = d.required_pair("max")?;
+        let offset = d.float("offset")?.unwrap_or(0.0);
+        let material = self.lookup_material(d)?;
+
+        if min.0 == max.0 || min.1 == max.1 {
+            return Err(d.error("rectangle must not be empty"));
+        }
+
+        self.place(
+            d,
+            Arc::new(AxisRect::new(plane, min, max, offset, material.clone())),
+            Some(material),
+            true,
+        )
+    }
+
+    fn quad(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let corner = d.required_vec3("corner")?;
+        let u = d.required_vec3("u")?;
+        let v = d.required_vec3("v")?;
+        let material = self.lookup_material(d)?;
+
+        if Vec3::cross_product(&u, &v).near_zero() {
+            return Err(d.error("quad edges must not be parallel"));
+        }
+
+        self.place(
+            d,
+            Arc::new(Quad::new(corner, u, v, material.clone())),
+            Some(material),
+            true,
+        )
+    }
+
+    fn disk(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let center = d.required_vec3("center")?;
+        let normal = d.required_vec3("normal")?;
+        let radius = d.required_float("radius")?;
+        let material = self.lookup_material(d)?;
+
+        if normal.near_zero() {
+            return Err(d.error("normal must not be zero"));
+        }
+        if radius <= 0.0 {
+            return Err(d.error("radius must be positive"));
+        }
+
+        self.place(
+            d,
+            Arc::new(Disk::new(center, normal, radius, material.clone())),
+            Some(material),
+            true,
+        )
+    }
+
+    fn cuboid(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let min = d.required_vec3("min")?;
+        let max = d.required_vec3("max")?;
+        let material = self.lookup_material(d)?;
+
+        if min.x() == max.x() || min.y() == max.y() || min.z() == max.z() {
+            return Err(d.error("box must not be flat"));
+        }
+
+        self.place(
+            d,
+            Arc::new(Cuboid::new(min, max, material.clone())),
+            Some(material),
+            true,
+        )
+    }
+
+    fn triangle(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let a = d.required_vec3("a")?;
+        let b = d.required_vec3("b")?;
+        let c = d.required_vec3("c")?;
+        let material = self.lookup_material(d)?;
+
+        if Vec3::cross_product(&(b - a), &(c - a)).near_zero() {
+            return Err(d.error("triangle must not be degenerate"));
+        }
+
+        self.place(
+            d,
+            Arc::new(Triangle::new(a, b, c, material.clone())),
+            Some(material),
+            true,
+        )
+    }
+
+    fn mesh(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let file = d.positional("mesh path")?;
+        let path = self.directory.join(file);
+        let material_name = d.word("material")?;
+        let material = match material_name {
+            Some(name) => Some(self.material_named(d, name)?),
+            None => None,
+        };
+
+        // Every placement of the same file and material shares one mesh.
+        let key = (path.clone(), material_name.map(str::to_string));
+        if let Some(mesh) = self.meshes.get(&key) {
+            let mesh = mesh.clone();
+            return self.place(d, mesh, material, false);
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let loaded = match extension.as_deref() {
+            Some("obj") => obj::load(&path, material.clone()),
+            Some("ply") => ply::load(&path, material.clone()),
+            _ => return Err(d.error("meshes must be .obj or .ply files")),
+        };
+        let mesh = match loaded {
+            Ok(mesh) => mesh,
+            Err(e) => return Err(d.error(format!("{}: {}", path.display(), e))),
+        };
+        if mesh.faces.is_empty() {
+            return Err(d.error(format!("{}: mesh has no faces", path.display())));
+        }
+
+        let mesh: Arc<dyn Hittable> = Arc::new(TriangleMesh::new(mesh));
+        self.meshes.insert(key, mesh.clone());
+        self.place(d, mesh, material, false)
+    }
+
+    fn medium(&mut self, d: &mut Directive) -> Result<(), SceneError> {
+        let grid = match d.positional("medium type")? {
+            "grid" => Some(self.directory.join(d.positional("grid path")?)),
+            "noise" => None,
+            kind => return Err(d.error(format!("unknown medium type `{}`", kind))),
+        };
+        let bounds = Aabb::new(d.required_vec3("min")?, d.required_vec3("max")?);
+        let density = d.required_float("density")?;
+        let material = self.lookup_material(d)?;
+
+        let size = *bounds.max() - *bounds.min();
+        if size.x() <= 0.0 || size.y() <= 0.0 || size.z() <= 0.0 {
+            return Err(d.error("a medium's box must have volume"));
+        }
+        if density < 0.0 {
+            return Err(d.error("density must not be negative"));
+        }
+
+        let field: Box<dyn DensityField> = match grid {
+            None => {
+                let frequency = d.float("frequency")?.unwrap_or(1.0);
+                let octaves = d.uint("octaves")?.unwrap_or(4);
+                let seed = d.uint("seed")?.unwrap_or(0);
+                let coverage = d.float("coverage")?.unwrap_or(0.0);
+                Box::new(NoiseField::new(
+                    seed as u64,
+                    frequency,
+                    octaves as u32,
+                    coverage,
+                    bounds,
+                ))
+            }
+            Some(path) => {
+                let v = d
+                    .values("resolution", 3)?
+                    .ok_or_else(|| d.error("missing `resolution`"))?;
+                let mut resolution = [0; 3];
+                for (n, token) in resolution.iter_mut().zip(v) {
+                    *n = match token.parse::<usize>() {
+                        Ok(n) if n > 0 => n,
+                        _ => return Err(d.error(format!("`resolution`: invalid size `{}`", token))),
+                    };
+                }
+                match VoxelGrid::load(&path, resolution, bounds) {
+                    Ok(grid) => Box::new(grid),
+                    Err(e) => return Err(d.error(format!("{}: {}", path.display(), e))),
+                }
+            }
+        };
+
+        self.scene
+            .media
+            .push(Box::new(HeterogeneousMedium::new(field, density, material)));
+        Ok(())
+    }
+
+    /* Reads `<prefix>scale x y z`, `<prefix>rotate x y z degrees` and
+     * `<prefix>translate x y z`, returning which of them were given.
+     */
+    fn placement(
+        d: &mut Directive,
+        prefix: &str,
+        placement: &mut Placement,
+    ) -> Result<bool, SceneError> {
+        let mut given = false;
+
+        let key = format!("{}scale", prefix);
+        if let Some(factors) = d.vec3(&key)? {
+            if factors.x() == 0.0 || factors.y() == 0.0 || factors.z() == 0.0 {
+                return Err(d.error(format!("`{}` must not be zero", key)));
+            }
+            placement.scale = factors;
+            given = true;
+        }
+
+        let key = format!("{}rotate", prefix);
+        if let Some(v) = d.values(&key, 4)? {
+            let axis = Vec3::new(
+                d.parse_float(&key, v[0])?,
+                d.parse_float(&key, v[1])?,
+                d.parse_float(&key, v[2])?,
+            );
+            if axis.near_zero() {
+                return Err(d.error("rotation axis must not be zero"));
+            }
+            placement.axis = axis;
+            placement.degrees = d.parse_float(&key, v[3])?;
+            given = true;
+        }
+
+        let key = format!("{}translate", prefix);
+        if let Some(offset) = d.vec3(&key)? {
+            placement.offset = offset;
+            given = true;
+        }
+
+        Ok(given)
+    }
+
+    /* Adds an object to the world, applying its optional `scale x y z`,
+     * `rotate x y z degrees` and `translate x y z`, in that order. The same
+     * keys prefixed with `end-` give the placement at time 1 for objects
+     * moving while the shutter is open. With `density` the object becomes
+     * the boundary of a medium scattering with its material. Objects of an
+     * emissive material are also added to the lights if they are
+     * `samplable`, not placed, not moving and not a medium.
+     */
+    fn place(
+        &mut self,
+        d: &mut Directive,
+        object: Arc<dyn Hittable>,
+        material: Option<Arc<dyn Material>>,
+        samplable: bool,
+    ) -> Result<(), SceneError> {
+        let mut start = Placement {
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            degrees: 0.0,
+            offset: Vec3::new(0.0, 0.0, 0.0),
+        };
+        let placed = Parser::placement(d, "", &mut start)?;
+
+        let mut end = start;
+        let moving = Parser::placement(d, "end-", &mut end)?;
+
+        let object: Arc<dyn Hittable> = if moving {
+            // rotating from or to no rotation keeps the given axis
+            if start.degrees == 0.0 {
+                start.axis = end.axis;
+            } else if end.degrees == 0.0 {
+                end.axis = start.axis;
+            }
+            match Animated::new(object, start, end) {
+                Some(a) => Arc::new(a),
+                None => return Err(d.error("motion passes through a singular transform")),
+            }
+        } else if placed {
+            match Transformed::new(object, start.matrix()) {
+                Some(t) => Arc::new(t),
+                None => return Err(d.error("transform must not be singular")),
+            }
+        } else {
+            object
+        };
+
+        let density = d.float("density")?;
+        let light = samplable
+            && !placed
+            && !moving
+            && density.is_none()
+            && material.as_ref().is_some_and(|m| m.is_emissive());
+
+        match density {
+            Some(density) if density <= 0.0 => {
+                return Err(d.error("density must be positive"));
+            }
+            Some(density) => match material {
+                Some(phase) => {
+                    let medium = ConstantMedium::new(object, density, phase);
+                    self.scene.media.push(Box::new(medium));
+                    return Ok(());
+                }
+                None => return Err(d.error("a medium needs a `material`")),
+            },
+            None => {}
+        }
+
+        if light {
+            self.scene.lights.add(object.clone());
+        }
+        self.scene.world.add(object);
+        Ok(())
+    }
+
+    fn lookup_material(&self, d: &mut Directive) -> Result<Arc<dyn Material>, SceneError> {
+        let name = d.required_word("material")?;
+        self.material_named(d, name)
+    }
+
+    fn material_named(&self, d: &Directive, name: &str) -> Result<Arc<dyn Material>, SceneError> {
+        match self.materials.get(name) {
+            Some(m) => Ok(m.clone()),
+            None => Err(d.error(format!("undefined material `{}`", name))),
+        }
+    }
+}
+
+/* How many values follow `key` in a `keyword` directive, or `None` if the
+ * directive takes no such key.
+ */
+fn arity(keyword: &str, key: &str) -> Option<usize> {
+    let object = matches!(
+        keyword,
+        "sphere" | "rect" | "quad" | "disk" | "box" | "triangle" | "mesh"
+    );
+
+    match (keyword, key) {
+        ("image", "width" | "height" | "aspect" | "samples" | "depth" | "roulette" | "sampler") => {
+            Some(1)
+        }
+        ("camera", "from" | "at" | "up") => Some(3),
+        ("camera", "fov" | "aperture" | "focus") => Some(1),
+        ("camera", "shutter") => Some(2),
+        ("background", "color") => Some(3),
+        ("texture", "color") => Some(3),
+        (
+            "texture",
+            "even" | "odd" | "size" | "columns" | "rows" | "low" | "high" | "scale" | "seed"
+            | "smoothing" | "octaves" | "colorspace" | "filter" | "wrap",
+        ) => Some(1),
+        ("material", "albedo" | "emit") => Some(3),
+        ("material", "texture" | "fuzz" | "intensity" | "g" | "ior") => Some(1),
+        ("sphere", "center" | "end-center") => Some(3),
+        ("sphere", "radius") => Some(1),
+        ("rect", "min" | "max") => Some(2),
+        ("rect", "offset") => Some(1),
+        ("quad", "corner" | "u" | "v") => Some(3),
+        ("disk", "center" | "normal") => Some(3),
+        ("disk", "radius") => Some(1),
+        ("box", "min" | "max") => Some(3),
+        ("triangle", "a" | "b" | "c") => Some(3),
+        ("medium", "min" | "max" | "resolution") => Some(3),
+        ("medium", "density" | "material" | "frequency" | "octaves" | "seed" | "coverage") => {
+            Some(1)
+        }
+        (_, "scale" | "translate" | "end-scale" | "end-translate") if object => Some(3),
+        (_, "rotate" | "end-rotate") if object => Some(4),
+        (_, "material" | "density") if object => Some(1),
+        _ => None,
+    }
+}
+
+/* The tokens following a keyword on one line. Arguments are consumed by the
+ * parser as it looks for them so anything left over can be reported.
+ * Positional arguments are read first; the first key looked up splits the
+ * rest into keys and their values, each key taking as many values as
+ * `arity` gives it, so a value is never mistaken for a key.
+ */
+struct Directive<'a> {
+    line: usize,
+    keyword: &'a str,
+    tokens: Vec<&'a str>,
+    used: Vec<bool>,
+    next_positional: usize,
+    /* The position of every key given, once the keys have been split off */
+    keys: Option<HashMap<&'a str, usize>>,
+}
+
+impl<'a> Directive<'a> {
+    fn new(line: usize, keyword: &'a str, tokens: Vec<&'a str>) -> Directive<'a> {
+        let used = vec![false; tokens.len()];
+        Directive {
+            line,
+            keyword,
+            tokens,
+            used,
+            next_positional: 0,
+            keys: None,
+        }
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> SceneError {
+        SceneError::Parse {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /* The next positional argument, if any, without consuming it */
+    fn peek(&self) -> Option<&'a str> {
+        match self.keys {
+            Some(_) => None,
+            None => self.tokens.get(self.next_positional).copied(),
+        }
+    }
+
+    fn positional(&mut self, what: &str) -> Result<&'a str, SceneError> {
+        let i = self.next_positional;
+        if self.keys.is_some() || i >= self.tokens.len() {
+            return Err(self.error(format!("missing {}", what)));
+        }
+
+        self.used[i] = true;
+        self.next_positional += 1;
+        Ok(self.tokens[i])
+    }
+
+    /* Splits the tokens after the positional arguments into keys and their
+     * values, rejecting unknown and repeated keys.
+     */
+    fn split_keys(&mut self) -> Result<&HashMap<&'a str, usize>, SceneError> {
+        if self.keys.is_none() {
+            let mut keys = HashMap::new();
+            let mut i = self.next_positional;
+
+            while i < self.tokens.len() {
+                let key = self.tokens[i];
+                let count = arity(self.keyword, key).ok_or_else(|| {
+                    self.error(format!("unknown key `{}` for `{}`", key, self.keyword))
+                })?;
+                if i + count >= self.tokens.len() {
+                    return Err(self.error(format!(
+                        "`{}` expects {} value{}",
+                        key,
+                        count,
+                        if count == 1 { "" } else { "s" }
+                    )));
+                }
+                if keys.insert(key, i).is_some() {
+                    return Err(self.error(format!("`{}` given twice", key)));
+                }
+                i += count + 1;
+            }
+
+            self.keys = Some(keys);
+        }
+
+        Ok(self.keys.as_ref().unwrap())
+    }
+
+    /* The `count` values of `key`, if given, marking them as used */
+    fn values(&mut self, key: &str, count: usize) -> Result<Option<Vec<&'a str>>, SceneError> {
+        debug_assert_eq!(arity(self.keyword, key), Some(count));
+        let position = match self.split_keys()?.get(key) {
+            Some(&p) => p,
+            None => return Ok(None),
+        };
+
+        for used in &mut self.used[position..=position + count] {
+            *used = true;
+        }
+
+        Ok(Some(self.tokens[position + 1..=position + count].to_vec()))
+    }
+
+    fn parse_float(&self, key: &str, token: &str) -> Result<f64, SceneError> {
+        match token.parse::<f64>() {
+            Ok(v) if v.is_finite() => Ok(v),
+            _ => Err(self.error(format!("`{}`: expected a number, found `{}`", key, token))),
+        }
+    }
+
+    fn word(&mut self, key: &str) -> Result<Option<&'a str>, SceneError> {
+        Ok(self.values(key, 1)?.map(|v| v[0]))
+    }
+
+    fn float(&mut self, key: &str) -> Result<Option<f64>, SceneError> {
+        match self.values(key, 1)? {
+            Some(v) => Ok(Some(self.parse_float(key, v[0])?)),
+            None => Ok(None),
+        }
+    }
+
+    fn uint(&mut self, key: &str) -> Result<Option<usize>, SceneError> {
+        match self.values(key, 1)? {
+            Some(v) => match v[0].parse::<usize>() {
+                Ok(n) => Ok(Some(n)),
+                Err(_) => Err(self.error(format!(
+                    "`{}`: expected a non-negative integer, found `{}`",
+                    key, v[0]
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn pair(&mut self, key: &str) -> Result<Option<(f64, f64)>, SceneError> {
+        match self.values(key, 2)? {
+            Some(v) => Ok(Some((
+                self.parse_float(key, v[0])?,
+                self.parse_float(key, v[1])?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn vec3(&mut self, key: &str) -> Result<Option<Vec3>, SceneError> {
+        match self.values(key, 3)? {
+            Some(v) => Ok(Some(Vec3::new(
+                self.parse_float(key, v[0])?,
+                self.parse_float(key, v[1])?,
+                self.parse_float(key, v[2])?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn required_word(&mut self, key: &str) -> Result<&'a str, SceneError> {
+        self.word(key)?
+            .ok_or_else(|| self.error(format!("missing `{}`", key)))
+    }
+
+    fn required_float(&mut self, key: &str) -> Result<f64, SceneError> {
+        self.float(key)?
+            .ok_or_else(|| self.error(format!("missing `{}`", key)))
+    }
+
+    fn required_pair(&mut self, key: &str) -> Result<(f64, f64), SceneError> {
+        self.pair(key)?
+            .ok_or_else(|| self.error(format!("missing `{}`", key)))
+    }
+
+    fn required_vec3(&mut self, key: &str) -> Result<Vec3, SceneError> {
+        self.vec3(key)?
+            .ok_or_else(|| self.error(format!("missing `{}`", key)))
+    }
+
+    fn finish(&self) -> Result<(), SceneError> {
+        match self.used.iter().position(|u| !u) {
+            Some(i) => Err(self.error(format!("unexpected `{}`", self.tokens[i]))),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use crate::cuboid::Cuboid;
+use crate::medium::{ConstantMedium, Medium};
+use crate::objects::{Dielectric, DiffuseLight, HittableList, Lambertian, Metal, Volumetric};
+use crate::phase::Isotropic;
+use crate::rect::{AxisRect, Plane};
+use crate::render::Background;
+use crate::sampler::SamplerKind;
+use crate::scene::{CameraSettings, ImageSettings, Scene};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::transform::Transformed;
+use crate::utility::Rng;
+use crate::vec3::{Color, Mat4, Point, Vec3};
+
+/* The built-in scene called `name`, with random layouts drawn from `seed` */
+pub fn builtin(name: &str, seed: u64) -> Option<Scene> {
+    match name {
+        "cover" => Some(cover(false, &mut Rng::new(seed))),
+        "bouncing" => Some(cover(true, &mut Rng::new(seed))),
+        "cornell" => Some(cornell(false)),
+        "cornell-smoke" => Some(cornell(true)),
+        _ => None,
+    }
+}
+
+/* The cover image: a large ground sphere, a grid of small randomly placed
+ * and randomly shaded spheres and three large ones in the middle. With
+ * `bouncing` the small diffuse spheres jump up while the shutter is open.
+ */
+pub fn cover(bouncing: bool, rng: &mut Rng) -> Scene {
+    let mut world = HittableList::new();
+    let lights = HittableList::new();
+
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let mat = rng.double();
+            let center = Point::new(
+                a as f64 + 0.9 * rng.double(),
+                0.2,
+                b as f64 + 0.9 * rng.double(),
+            );
+
+            if (center - Point::new(4.0, 0.2, 0.0)).length() <= 0.9 {
+                continue;
+            }
+
+            if mat < 0.8 {
+                let albedo = Color::random(rng) * Color::random(rng);
+                let material = Arc::new(Lambertian::new(albedo));
+                if bouncing {
+                    let end = center + Vec3::new(0.0, rng.range(0.0, 0.5), 0.0);
+                    world.add(Arc::new(MovingSphere::new(center, end, 0.2, material)));
+                } else {
+                    world.add(Arc::new(Sphere::new(center, 0.2, material)));
+                }
+            } else if mat < 0.95 {
+                let albedo = Color::random_rng(rng, 0.5, 1.0);
+                let fuzz = rng.range(0.0, 0.5);
+                world.add(Arc::new(Sphere::new(
+                    center,
+                    0.2,
+                    Arc::new(Metal::new(albedo, fuzz)),
+                )));
+            } else {
+                world.add(Arc::new(Sphere::new(
+                    center,
+                    0.2,
+                    Arc::new(Dielectric::new(1.5)),
+                )));
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(
+        Point::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+
+    Scene {
+        image: ImageSettings::default(),
+        camera: CameraSettings {
+            look_from: Point::new(13.0, 2.0, 3.0),
+            look_at: Point::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            focus_distance: Some(10.0),
+            shutter: if bouncing { (0.0, 1.0) } else { (0.0, 0.0) },
+        },
+        background: Background::Sky,
+        world,
+        media: Vec::new(),
+        lights,
+    }
+}
+
+/* The Cornell box: a closed room with a red and a green wall, lit by a
+ * single ceiling light, holding two white boxes, or with `smoke` a block
+ * of dark smoke and one of white fog.
+ */
+pub fn cornell(smoke: bool) -> Scene {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0), 15.0));
+
+    let walls = [
+        (Plane::Yz, 555.0, green),
+        (Plane::Yz, 0.0, red),
+        (Plane::Xz, 0.0, white.clone()),
+        (Plane::Xz, 555.0, white.clone()),
+        (Plane::Xy, 555.0, white.clone()),
+    ];
+    for (plane, offset, material) in walls.iter() {
+        world.add(Arc::new(AxisRect::new(
+            *plane,
+            (0.0, 0.0),
+            (555.0, 555.0),
+            *offset,
+            material.clone(),
+        )));
+    }
+    let ceiling_light = Arc::new(AxisRect::new(
+        Plane::Xz,
+        (213.0, 227.0),
+        (343.0, 332.0),
+        554.0,
+        light,
+    ));
+    let mut lights = HittableList::new();
+    lights.add(ceiling_light.clone());
+    world.add(ceiling_light);
+
+    // the short box turned left and the tall one right, as in the original
+    let boxes = [
+        (
+            Point::new(165.0, 165.0, 165.0),
+            -18.0,
+            Vec3::new(130.0, 0.0, 65.0),
+        ),
+        (
+            Point::new(165.0, 330.0, 165.0),
+            15.0,
+            Vec3::new(265.0, 0.0, 295.0),
+        ),
+    ];
+    let smokes = [Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)];
+    let mut media: Vec<Box<dyn Medium>> = Vec::new();
+    for ((size, degrees, offset), color) in boxes.iter().zip(smokes.iter()) {
+        let shape = Arc::new(Cuboid::new(Point::new(0.0, 0.0, 0.0), *size, white.clone()));
+        let placement =
+            Mat4::translation(offset) * Mat4::rotation(&Vec3::new(0.0, 1.0, 0.0), *degrees);
+        let placed = Arc::new(Transformed::new(shape, placement).unwrap());
+
+        if smoke {
+            let phase = Arc::new(Volumetric::new(*color, Arc::new(Isotropic)));
+            media.push(Box::new(ConstantMedium::new(placed, 0.01, phase)));
+        } else {
+            world.add(placed);
+        }
+    }
+
+    Scene {
+        image: ImageSettings {
+            width: 600,
+            height: 600,
+            samples_per_pixel: 200,
+            max_depth: 50,
+            roulette_depth: 3,
+            sampler: SamplerKind::Sobol,
+        },
+        camera: CameraSettings {
+            look_from: Point::new(278.0, 278.0, -800.0),
+            look_at: Point::new(278.0, 278.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+            focus_distance: None,
+            shutter: (0.0, 0.0),
+        },
+        background: Background::Color(Color::new(0.0, 0.0, 0.0)),
+        world,
+        media,
+        lights,
+    }
+}
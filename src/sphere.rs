@@ -1,55 +1,173 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::aabb::Aabb;
 use crate::objects::{HitRecord, Hittable, Material};
 use crate::ray::Ray;
-use crate::vec3::{Color, Point, Vec3};
+use crate::sampling::{self, Onb};
+use crate::vec3::{Point, Vec3};
 
 pub struct Sphere {
     center: Point,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f64, material: Rc<dyn Material>) -> Sphere {
+    pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
             material,
         }
     }
+
+    /* Maps a point on the unit sphere to (u, v) in [0, 1]: u is the angle
+     * around the Y axis starting from -X, v the angle from -Y up to +Y.
+     */
+    fn uv(p: &Point) -> (f64, f64) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+
+        (
+            phi / (2.0 * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
+    }
 }
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord>) {
-        let oc = *r.get_origin() - self.center;
+/* Intersects the sphere at `center` of `radius`, shared by still and moving
+ * spheres.
+ */
+fn hit_sphere<'a>(
+    center: Point,
+    radius: f64,
+    material: &'a dyn Material,
+    r: &Ray,
+    t: (f64, f64),
+) -> (bool, Option<HitRecord<'a>>) {
+    let oc = *r.get_origin() - center;
 
-        let a = r.get_direction().length_squared();
-        let half_b = Vec3::dot(&oc, r.get_direction());
-        let c = oc.length_squared() - self.radius * self.radius;
+    let a = r.get_direction().length_squared();
+    let half_b = Vec3::dot(&oc, r.get_direction());
+    let c = oc.length_squared() - radius * radius;
 
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return (false, None);
+    }
+
+    let sqrtd = discriminant.sqrt();
+
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t.0 || t.1 < root {
+        root = (-half_b + sqrtd) / a;
+        if root < t.0 || t.1 < root {
             return (false, None);
         }
+    }
 
-        let sqrtd = discriminant.sqrt();
+    let t = root;
+    let point = r.at(t);
+    let outward_normal = (point - center) / radius;
 
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t.0 || t.1 < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t.0 || t.1 < root {
-                return (false, None);
-            }
+    let mut rec = HitRecord::new(
+        point,
+        outward_normal,
+        t,
+        Sphere::uv(&outward_normal),
+        false,
+        material,
+    );
+    rec.set_face_normal(r, outward_normal);
+
+    (true, Some(rec))
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        hit_sphere(self.center, self.radius, self.material.as_ref(), r, t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    /* Directions are picked uniformly within the cone the sphere fills as
+     * seen from `origin`, or over all directions from inside it.
+     */
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return sampling::uniform_sphere_pdf();
         }
 
-        let t = root;
-        let point = r.at(t);
-        let outward_normal = (point - self.center) / self.radius;
+        let ray = Ray::new(*origin, *direction, 0.0);
+        if !self.hit(&ray, (0.001, f64::INFINITY)).0 {
+            return 0.0;
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        sampling::uniform_cone_pdf(cos_theta_max)
+    }
+
+    fn random(&self, origin: &Point, u: (f64, f64)) -> Vec3 {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return sampling::uniform_sphere(u);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let local = sampling::uniform_cone(u, cos_theta_max);
+        Onb::new(&to_center.unit()).local(&local)
+    }
+}
+
+/* A sphere moving in a straight line from `center0` at time 0 to `center1`
+ * at time 1. Outside of that interval it stays at the nearest end.
+ */
+pub struct MovingSphere {
+    center0: Point,
+    center1: Point,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point,
+        center1: Point,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            radius,
+            material,
+        }
+    }
+
+    fn center(&self, time: f64) -> Point {
+        let s = time.clamp(0.0, 1.0);
+        self.center0 + s * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let center = self.center(r.get_time());
+        hit_sphere(center, self.radius, self.material.as_ref(), r, t)
+    }
 
-        let mut rec = HitRecord::new(point, outward_normal, t, false, self.material.clone());
-        rec.set_face_normal(r, outward_normal);
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        let start = Aabb::new(self.center0 - extent, self.center0 + extent);
+        let end = Aabb::new(self.center1 - extent, self.center1 + extent);
 
-        return (true, Some(rec));
+        Some(Aabb::surrounding(&start, &end))
     }
 }
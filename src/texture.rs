@@ -0,0 +1,387 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::hdr;
+use crate::noise::{Perlin, Worley};
+use crate::png;
+use crate::ppm;
+use crate::utility;
+use crate::vec3::{Color, Point};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        self.color
+    }
+}
+
+/* Alternates between two textures in cubes of side `size` filling space,
+ * independent of how the surface is parametrized.
+ */
+pub struct CheckerTexture {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    inverse_size: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, size: f64) -> CheckerTexture {
+        CheckerTexture {
+            even,
+            odd,
+            inverse_size: 1.0 / size,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let x = (self.inverse_size * p.x()).floor() as i64;
+        let y = (self.inverse_size * p.y()).floor() as i64;
+        let z = (self.inverse_size * p.z()).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/* Alternates between two textures on a grid of `columns` by `rows` squares in
+ * surface (u, v) coordinates.
+ */
+pub struct UvCheckerTexture {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    columns: f64,
+    rows: f64,
+}
+
+impl UvCheckerTexture {
+    pub fn new(
+        even: Arc<dyn Texture>,
+        odd: Arc<dyn Texture>,
+        columns: f64,
+        rows: f64,
+    ) -> UvCheckerTexture {
+        UvCheckerTexture {
+            even,
+            odd,
+            columns,
+            rows,
+        }
+    }
+}
+
+impl Texture for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let column = (u * self.columns).floor() as i64;
+        let row = (v * self.rows).floor() as i64;
+
+        if (column + row) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/* Mixes two textures, all `low` at `t` = 0 and all `high` at 1 */
+fn blend(low: &dyn Texture, high: &dyn Texture, t: f64, u: f64, v: f64, p: &Point) -> Color {
+    let t = utility::clamp(t, 0.0, 1.0);
+    (1.0 - t) * low.value(u, v, p) + t * high.value(u, v, p)
+}
+
+/* Patterns made from Perlin noise */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoisePattern {
+    /* Soft fractal noise */
+    Noise,
+    /* Sum of the magnitudes of the noise octaves, cloudy with sharp creases */
+    Turbulence,
+    /* Bands along x warped by turbulence */
+    Marble,
+    /* Rings around the y axis warped by turbulence */
+    Wood,
+}
+
+/* Blends between two textures by a noise pattern. `scale` is the pattern's
+ * frequency in space and `octaves` the number of noise layers summed.
+ */
+pub struct NoiseTexture {
+    noise: Perlin,
+    pattern: NoisePattern,
+    scale: f64,
+    octaves: u32,
+    low: Arc<dyn Texture>,
+    high: Arc<dyn Texture>,
+}
+
+impl NoiseTexture {
+    pub fn new(
+        noise: Perlin,
+        pattern: NoisePattern,
+        scale: f64,
+        octaves: u32,
+        low: Arc<dyn Texture>,
+        high: Arc<dyn Texture>,
+    ) -> NoiseTexture {
+        NoiseTexture {
+            noise,
+            pattern,
+            scale,
+            octaves,
+            low,
+            high,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let q = self.scale * *p;
+        let t = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.noise.fbm(&q, self.octaves)),
+            NoisePattern::Turbulence => self.noise.turbulence(&q, self.octaves),
+            // The warp uses the unscaled position, so `scale` changes how
+            // many bands or rings there are but not how much they wiggle.
+            NoisePattern::Marble => {
+                let turbulence = self.noise.turbulence(p, self.octaves);
+                0.5 * (1.0 + (q.x() + 10.0 * turbulence).sin())
+            }
+            NoisePattern::Wood => {
+                let turbulence = self.noise.turbulence(p, self.octaves);
+                let rings = (q.x() * q.x() + q.z() * q.z()).sqrt() + turbulence;
+                rings - rings.floor()
+            }
+        };
+
+        blend(self.low.as_ref(), self.high.as_ref(), t, u, v, p)
+    }
+}
+
+/* Blends between two textures by the distance to the nearest Worley feature
+ * point, `low` at the points and `high` far from them, in cells about
+ * 1 / `scale` across.
+ */
+pub struct WorleyTexture {
+    noise: Worley,
+    scale: f64,
+    low: Arc<dyn Texture>,
+    high: Arc<dyn Texture>,
+}
+
+impl WorleyTexture {
+    pub fn new(
+        noise: Worley,
+        scale: f64,
+        low: Arc<dyn Texture>,
+        high: Arc<dyn Texture>,
+    ) -> WorleyTexture {
+        WorleyTexture {
+            noise,
+            scale,
+            low,
+            high,
+        }
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> Color {
+        let t = self.noise.noise(&(self.scale * *p));
+        blend(self.low.as_ref(), self.high.as_ref(), t, u, v, p)
+    }
+}
+
+/* The sRGB transfer function, from encoded to linear values */
+pub fn srgb_decode(v: f64) -> f64 {
+    if v <= 0.040_45 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+/* What lookups outside of [0, 1] return */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    fn index(&self, i: i64, size: usize) -> usize {
+        let n = size as i64;
+        let wrapped = match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+        };
+
+        wrapped as usize
+    }
+}
+
+/* How 8 and 16 bit image samples are turned into linear values. Color maps
+ * are usually sRGB encoded, data such as roughness or masks linear.
+ * Floating point images are always linear.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+/* Linear colors on a grid of texels, row by row from the top, mapped so that
+ * (0, 0) is the bottom left corner and (1, 1) the top right.
+ */
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    filter: Filter,
+    wrap: Wrap,
+}
+
+impl ImageTexture {
+    pub fn new(
+        width: usize,
+        height: usize,
+        texels: Vec<Color>,
+        filter: Filter,
+        wrap: Wrap,
+    ) -> io::Result<ImageTexture> {
+        if width.checked_mul(height) != Some(texels.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "image size does not match its texel count",
+            ));
+        }
+
+        Ok(ImageTexture {
+            width,
+            height,
+            texels,
+            filter,
+            wrap,
+        })
+    }
+
+    /* Converts integer samples, three per texel and each at most
+     * `max_value`, to linear colors.
+     */
+    fn decode(samples: &[u16], max_value: u16, color_space: ColorSpace) -> Vec<Color> {
+        let scale = 1.0 / max_value as f64;
+        let decode = |s: u16| match color_space {
+            ColorSpace::Srgb => srgb_decode(s as f64 * scale),
+            ColorSpace::Linear => s as f64 * scale,
+        };
+
+        samples
+            .chunks(3)
+            .map(|s| Color::new(decode(s[0]), decode(s[1]), decode(s[2])))
+            .collect()
+    }
+
+    /* Loads a PNG, PPM or Radiance HDR image, telling them apart by their
+     * contents rather than the file name.
+     */
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        color_space: ColorSpace,
+        filter: Filter,
+        wrap: Wrap,
+    ) -> io::Result<ImageTexture> {
+        let data = fs::read(path)?;
+
+        let (width, height, texels) = if data.starts_with(b"\x89PNG") {
+            let (width, height, samples, max_value) = png::read(&data)?;
+            (
+                width,
+                height,
+                ImageTexture::decode(&samples, max_value, color_space),
+            )
+        } else if data.starts_with(b"#?") {
+            hdr::read(&data)?
+        } else if data.starts_with(b"P3") || data.starts_with(b"P6") {
+            let (width, height, samples, max_value) = ppm::read(&data)?;
+            (
+                width,
+                height,
+                ImageTexture::decode(&samples, max_value, color_space),
+            )
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unrecognized image format; expected PNG, PPM or Radiance HDR",
+            ));
+        };
+
+        ImageTexture::new(width, height, texels, filter, wrap)
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let x = self.wrap.index(i, self.width);
+        let y = self.wrap.index(j, self.height);
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point) -> Color {
+        if self.texels.is_empty() || !u.is_finite() || !v.is_finite() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // texel space, with rows counted from the top
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // texel centers sit at half-integer coordinates
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (i, j) = (x.floor(), y.floor());
+                let (fx, fy) = (x - i, y - j);
+                let (i, j) = (i as i64, j as i64);
+
+                let top = (1.0 - fx) * self.texel(i, j) + fx * self.texel(i + 1, j);
+                let bottom = (1.0 - fx) * self.texel(i, j + 1) + fx * self.texel(i + 1, j + 1);
+                (1.0 - fy) * top + fy * bottom
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::utility;
+use crate::vec3::Color;
+
+/* Operators compressing linear scene radiance into the displayable 0..1
+ * range. Reinhard variants work on luminance to keep hues intact; the
+ * filmic curves are applied per channel like their reference versions.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operator {
+    Clamp,
+    Reinhard,
+    /* Reinhard with the luminance that maps to pure white */
+    ExtendedReinhard(f64),
+    /* Narkowicz's fit of the ACES reference rendering transform */
+    Aces,
+    /* John Hable's Uncharted 2 curve */
+    Hable,
+}
+
+impl Operator {
+    pub fn from_name(name: &str, white: f64) -> Option<Operator> {
+        match name {
+            "clamp" => Some(Operator::Clamp),
+            "reinhard" => Some(Operator::Reinhard),
+            "reinhard-extended" => Some(Operator::ExtendedReinhard(white)),
+            "aces" => Some(Operator::Aces),
+            "hable" => Some(Operator::Hable),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ToneMapping {
+    pub operator: Operator,
+    /* In stops; every stop doubles the incoming radiance */
+    pub exposure: f64,
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping {
+            operator: Operator::Clamp,
+            exposure: 0.0,
+        }
+    }
+}
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+fn scale_luminance(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(&c);
+    if l <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    c * (f(l) / l)
+}
+
+fn per_channel(c: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(c.x()), f(c.y()), f(c.z()))
+}
+
+fn aces(x: f64) -> f64 {
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    (x * (a * x + b)) / (x * (c * x + d) + e)
+}
+
+fn hable_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn hable(x: f64) -> f64 {
+    let exposure_bias = 2.0;
+    let white = 11.2;
+    hable_partial(exposure_bias * x) / hable_partial(white)
+}
+
+/* The sRGB transfer function, from linear to encoded values */
+pub fn srgb_encode(v: f64) -> f64 {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ToneMapping {
+    /* Maps a linear color to linear display values in 0..1 */
+    pub fn apply(&self, color: Color) -> Color {
+        let c = per_channel(color * 2f64.powf(self.exposure), |v| v.max(0.0));
+
+        let mapped = match self.operator {
+            Operator::Clamp => c,
+            Operator::Reinhard => scale_luminance(c, |l| l / (1.0 + l)),
+            Operator::ExtendedReinhard(white) => {
+                scale_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            Operator::Aces => per_channel(c, aces),
+            Operator::Hable => per_channel(c, hable),
+        };
+
+        per_channel(mapped, |v| utility::clamp(v, 0.0, 1.0))
+    }
+
+    /* Tone maps and sRGB encodes a color for an 8 or 16 bit image */
+    pub fn display(&self, color: Color) -> Color {
+        per_channel(self.apply(color), srgb_encode)
+    }
+}
@@ -0,0 +1,203 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::objects::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::{Mat4, Point, Vec3};
+
+/* Places an object in the world with an affine transform. Rays are moved
+ * into the object's space instead of moving the object, so one object can
+ * be shared by many instances.
+ */
+pub struct Transformed {
+    object: Arc<dyn Hittable>,
+    to_world: Mat4,
+    to_object: Mat4,
+    /* Transposed inverse, which keeps normals perpendicular to surfaces */
+    normal_to_world: Mat4,
+    bounds: Option<Aabb>,
+}
+
+impl Transformed {
+    /* None if `to_world` cannot be inverted */
+    pub fn new(object: Arc<dyn Hittable>, to_world: Mat4) -> Option<Transformed> {
+        let to_object = to_world.inverse()?;
+
+        let bounds = object.bounding_box().map(|b| {
+            let (min, max) = (b.min(), b.max());
+            let mut bounds = Aabb::empty();
+            for corner in 0..8 {
+                let p = Point::new(
+                    if corner & 1 == 0 { min.x() } else { max.x() },
+                    if corner & 2 == 0 { min.y() } else { max.y() },
+                    if corner & 4 == 0 { min.z() } else { max.z() },
+                );
+                bounds.grow(&to_world.transform_point(&p));
+            }
+            bounds
+        });
+
+        Some(Transformed {
+            object,
+            to_world,
+            to_object,
+            normal_to_world: to_object.transpose(),
+            bounds,
+        })
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        // The direction is not normalized, so t means the same in both spaces.
+        let local = Ray::new(
+            self.to_object.transform_point(r.get_origin()),
+            self.to_object.transform_vector(r.get_direction()),
+            r.get_time(),
+        );
+
+        match self.object.hit(&local, t) {
+            (true, Some(mut rec)) => {
+                rec.point = self.to_world.transform_point(&rec.point);
+                // Facing is unchanged: the transformed normal and direction
+                // have the same dot product as the untransformed ones.
+                rec.normal = self.normal_to_world.transform_vector(&rec.normal).unit();
+                (true, Some(rec))
+            }
+            _ => (false, None),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+/* Scale, then rotation about an axis through the origin, then translation;
+ * the parts of a transform that can be interpolated over time.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Placement {
+    pub scale: Vec3,
+    pub axis: Vec3,
+    pub degrees: f64,
+    pub offset: Vec3,
+}
+
+impl Placement {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(&self.offset)
+            * Mat4::rotation(&self.axis, self.degrees)
+            * Mat4::scaling(&self.scale)
+    }
+
+    fn inverse_matrix(&self) -> Mat4 {
+        let s = self.scale;
+        Mat4::scaling(&Vec3::new(1.0 / s.x(), 1.0 / s.y(), 1.0 / s.z()))
+            * Mat4::rotation(&self.axis, -self.degrees)
+            * Mat4::translation(&-self.offset)
+    }
+
+    fn lerp(&self, end: &Placement, s: f64) -> Placement {
+        Placement {
+            scale: self.scale + s * (end.scale - self.scale),
+            axis: self.axis + s * (end.axis - self.axis),
+            degrees: self.degrees + s * (end.degrees - self.degrees),
+            offset: self.offset + s * (end.offset - self.offset),
+        }
+    }
+}
+
+/* An object whose placement changes from `start` at time 0 to `end` at
+ * time 1, holding still outside of that interval.
+ */
+pub struct Animated {
+    object: Arc<dyn Hittable>,
+    start: Placement,
+    end: Placement,
+    bounds: Option<Aabb>,
+}
+
+impl Animated {
+    /* None if either placement scales by zero or the rotation axis
+     * vanishes in between.
+     */
+    pub fn new(object: Arc<dyn Hittable>, start: Placement, end: Placement) -> Option<Animated> {
+        let steps = 32;
+        let placements: Vec<Placement> = (0..=steps)
+            .map(|i| start.lerp(&end, i as f64 / steps as f64))
+            .collect();
+        for p in &placements {
+            let s = p.scale;
+            if s.x() == 0.0 || s.y() == 0.0 || s.z() == 0.0 || p.axis.near_zero() {
+                return None;
+            }
+        }
+
+        // The box over the whole motion: the object's corners at evenly
+        // spaced times, padded by how far a corner moves between two of them.
+        let bounds = object.bounding_box().map(|b| {
+            let (min, max) = (b.min(), b.max());
+            let corners: Vec<Point> = (0..8)
+                .map(|corner| {
+                    Point::new(
+                        if corner & 1 == 0 { min.x() } else { max.x() },
+                        if corner & 2 == 0 { min.y() } else { max.y() },
+                        if corner & 4 == 0 { min.z() } else { max.z() },
+                    )
+                })
+                .collect();
+
+            let mut bounds = Aabb::empty();
+            let mut step = 0.0f64;
+            let mut previous: Option<Vec<Point>> = None;
+            for p in &placements {
+                let m = p.matrix();
+                let moved: Vec<Point> = corners.iter().map(|c| m.transform_point(c)).collect();
+                for (i, c) in moved.iter().enumerate() {
+                    bounds.grow(c);
+                    if let Some(previous) = &previous {
+                        step = step.max((*c - previous[i]).length());
+                    }
+                }
+                previous = Some(moved);
+            }
+
+            let pad = Vec3::new(step, step, step);
+            Aabb::new(*bounds.min() - pad, *bounds.max() + pad)
+        });
+
+        Some(Animated {
+            object,
+            start,
+            end,
+            bounds,
+        })
+    }
+}
+
+impl Hittable for Animated {
+    fn hit(&self, r: &Ray, t: (f64, f64)) -> (bool, Option<HitRecord<'_>>) {
+        let placement = self.start.lerp(&self.end, r.get_time().clamp(0.0, 1.0));
+        let to_object = placement.inverse_matrix();
+
+        let local = Ray::new(
+            to_object.transform_point(r.get_origin()),
+            to_object.transform_vector(r.get_direction()),
+            r.get_time(),
+        );
+
+        match self.object.hit(&local, t) {
+            (true, Some(mut rec)) => {
+                rec.point = placement.matrix().transform_point(&rec.point);
+                rec.normal = to_object.transpose().transform_vector(&rec.normal).unit();
+                (true, Some(rec))
+            }
+            _ => (false, None),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
@@ -1,4 +1,43 @@
-use rand;
+use rand::rngs::StdRng;
+use rand::{Rng as _, SeedableRng};
+
+/* A random number generator. Everything random takes one explicitly rather
+ * than reaching for a global, so that an image depends only on the seeds
+ * its generators start from and not on threads or the order of work.
+ */
+pub struct Rng(StdRng);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(StdRng::seed_from_u64(seed))
+    }
+
+    /* Uniform in [0, 1) */
+    pub fn double(&mut self) -> f64 {
+        self.0.gen::<f64>()
+    }
+
+    /* Uniform in [min, max) */
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.double()
+    }
+
+    /* A point uniform in the unit square [0, 1)^2 */
+    pub fn pair(&mut self) -> (f64, f64) {
+        (self.double(), self.double())
+    }
+}
+
+/* SplitMix64 finalizer, turning neighbouring integers such as
+ * pixel indices or lattice coordinates into well separated, random looking
+ * 64 bit values.
+ */
+pub fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
 
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x < min {
@@ -9,11 +48,7 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
         return max;
     }
 
-    return x;
-}
-
-pub fn random(min: f64, max: f64) -> f64 {
-    min + (max - min) * rand::random::<f64>()
+    x
 }
 
 pub fn degrees_to_radians(degrees: f64) -> f64 {
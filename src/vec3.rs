@@ -1,64 +1,25 @@
-use rand;
 use std::ops;
 
-use crate::utility;
+use crate::utility::{self, Rng};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec3(f64, f64, f64);
 
 impl Vec3 {
-    pub fn random() -> Vec3 {
-        Vec3::new(
-            rand::random::<f64>(),
-            rand::random::<f64>(),
-            rand::random::<f64>(),
-        )
+    pub fn random(rng: &mut Rng) -> Vec3 {
+        Vec3::new(rng.double(), rng.double(), rng.double())
     }
 
-    pub fn random_rng(min: f64, max: f64) -> Vec3 {
+    pub fn random_rng(rng: &mut Rng, min: f64, max: f64) -> Vec3 {
         Vec3::new(
-            utility::random(min, max),
-            utility::random(min, max),
-            utility::random(min, max),
+            rng.range(min, max),
+            rng.range(min, max),
+            rng.range(min, max),
         )
     }
 
-    pub fn random_in_unit_sphere() -> Vec3 {
-        loop {
-            let p = Vec3::random_rng(-1.0, 1.0);
-            if p.length_squared() >= 1.0 {
-                continue;
-            }
-
-            return p;
-        }
-    }
-
-    pub fn random_unit_vector() -> Vec3 {
-        return Vec3::random_in_unit_sphere().unit();
-    }
-
-    pub fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3::new(utility::random(-1.0, 1.0), utility::random(-1.0, 1.0), 0.0);
-            if p.length_squared() >= 1.0 {
-                continue;
-            }
-            return p;
-        }
-    }
-
-    pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
-        let in_unit_sphere = Vec3::random_in_unit_sphere();
-        if Vec3::dot(&in_unit_sphere, &normal) > 0.0 {
-            return in_unit_sphere;
-        } else {
-            return -in_unit_sphere;
-        }
-    }
-
     pub fn new(x: f64, y: f64, z: f64) -> Vec3 {
-        return Vec3(x, y, z);
+        Vec3(x, y, z)
     }
 
     pub fn x(&self) -> f64 {
@@ -83,7 +44,7 @@ impl Vec3 {
 
     pub fn near_zero(&self) -> bool {
         let s = 1.0e-8;
-        return (self.0.abs() < s) && (self.1.abs() < s) && (self.2.abs() < s);
+        (self.0.abs() < s) && (self.1.abs() < s) && (self.2.abs() < s)
     }
 
     pub fn unit(&self) -> Vec3 {
@@ -96,14 +57,22 @@ impl Vec3 {
 
     pub fn refract(v: &Vec3, n: &Vec3, e: f64) -> Vec3 {
         let mut cos_theta = Vec3::dot(&-(*v), n);
-        if (cos_theta > 1.0) {
+        if cos_theta > 1.0 {
             cos_theta = 1.0;
         }
 
         let perpendicular = e * (*v + cos_theta * *n);
         let parallel = -(1.0 - perpendicular.length_squared()).abs().sqrt() * *n;
 
-        return perpendicular + parallel;
+        perpendicular + parallel
+    }
+
+    pub fn min(u: &Vec3, v: &Vec3) -> Vec3 {
+        Vec3::new(u.0.min(v.0), u.1.min(v.1), u.2.min(v.2))
+    }
+
+    pub fn max(u: &Vec3, v: &Vec3) -> Vec3 {
+        Vec3::new(u.0.max(v.0), u.1.max(v.1), u.2.max(v.2))
     }
 
     pub fn dot(u: &Vec3, v: &Vec3) -> f64 {
@@ -122,6 +91,19 @@ impl Vec3 {
 pub type Point = Vec3;
 pub type Color = Vec3;
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.0,
+            1 => &self.1,
+            2 => &self.2,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
 // Unary
 impl ops::Neg for Vec3 {
     type Output = Self;
@@ -261,3 +243,153 @@ impl ops::DivAssign<f64> for Vec3 {
         self.2 /= other;
     }
 }
+
+/* Row-major 4x4 matrix for affine transforms of points and vectors */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4([[f64; 4]; 4]);
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        Mat4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: &Vec3) -> Mat4 {
+        Mat4([
+            [1.0, 0.0, 0.0, offset.0],
+            [0.0, 1.0, 0.0, offset.1],
+            [0.0, 0.0, 1.0, offset.2],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: &Vec3) -> Mat4 {
+        Mat4([
+            [factors.0, 0.0, 0.0, 0.0],
+            [0.0, factors.1, 0.0, 0.0],
+            [0.0, 0.0, factors.2, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /* Counterclockwise rotation by `degrees` about `axis` when looking
+     * down the axis towards the origin.
+     */
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Mat4 {
+        let a = axis.unit();
+        let (sin, cos) = utility::degrees_to_radians(degrees).sin_cos();
+        let k = 1.0 - cos;
+
+        Mat4([
+            [
+                cos + a.0 * a.0 * k,
+                a.0 * a.1 * k - a.2 * sin,
+                a.0 * a.2 * k + a.1 * sin,
+                0.0,
+            ],
+            [
+                a.1 * a.0 * k + a.2 * sin,
+                cos + a.1 * a.1 * k,
+                a.1 * a.2 * k - a.0 * sin,
+                0.0,
+            ],
+            [
+                a.2 * a.0 * k - a.1 * sin,
+                a.2 * a.1 * k + a.0 * sin,
+                cos + a.2 * a.2 * k,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in self.0.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                t[j][i] = *value;
+            }
+        }
+
+        Mat4(t)
+    }
+
+    /* Gauss-Jordan elimination with partial pivoting; None if singular */
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut m = self.0;
+        let mut inverse = Mat4::identity().0;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+                .unwrap();
+            if m[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for j in 0..4 {
+                m[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = m[row][column];
+                for j in 0..4 {
+                    m[row][j] -= factor * m[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+
+        Some(Mat4(inverse))
+    }
+
+    pub fn transform_point(&self, p: &Point) -> Point {
+        let m = &self.0;
+        let x = m[0][0] * p.0 + m[0][1] * p.1 + m[0][2] * p.2 + m[0][3];
+        let y = m[1][0] * p.0 + m[1][1] * p.1 + m[1][2] * p.2 + m[1][3];
+        let z = m[2][0] * p.0 + m[2][1] * p.1 + m[2][2] * p.2 + m[2][3];
+        let w = m[3][0] * p.0 + m[3][1] * p.1 + m[3][2] * p.2 + m[3][3];
+
+        if w == 1.0 {
+            Point::new(x, y, z)
+        } else {
+            Point::new(x / w, y / w, z / w)
+        }
+    }
+
+    /* Transforms a direction, ignoring the translation */
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3::new(
+            m[0][0] * v.0 + m[0][1] * v.1 + m[0][2] * v.2,
+            m[1][0] * v.0 + m[1][1] * v.1 + m[1][2] * v.2,
+            m[2][0] * v.0 + m[2][1] * v.1 + m[2][2] * v.2,
+        )
+    }
+}
+
+impl ops::Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self::Output {
+        let mut product = [[0.0; 4]; 4];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[i][k] * other.0[k][j]).sum();
+            }
+        }
+
+        Mat4(product)
+    }
+}
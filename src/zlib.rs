@@ -0,0 +1,479 @@
+/* Minimal zlib (RFC 1950) stream support for the PNG codec. Compression
+ * uses greedy LZ77 matching with the fixed Huffman codes of deflate
+ * (RFC 1951), which is simple and gets most of the gain on image data.
+ * Decompression handles every block type.
+ */
+
+use std::io;
+
+const WINDOW_SIZE: usize = 1 << 15;
+const HASH_BITS: usize = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> BitWriter {
+        BitWriter {
+            out,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /* Deflate packs values starting at the least significant bit. */
+    fn write_bits(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /* Huffman codes are stored most significant bit first. */
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn write_literal(w: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => w.write_code(0x30 + symbol, 8),
+        144..=255 => w.write_code(0x190 + symbol - 144, 9),
+        256..=279 => w.write_code(symbol - 256, 7),
+        _ => w.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, length: usize, distance: usize) {
+    let l = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(w, 257 + l as u32);
+    w.write_bits(
+        (length - LENGTH_BASE[l] as usize) as u32,
+        LENGTH_EXTRA[l] as u32,
+    );
+
+    let d = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    w.write_code(d as u32, 5);
+    w.write_bits(
+        (distance - DISTANCE_BASE[d] as usize) as u32,
+        DISTANCE_EXTRA[d] as u32,
+    );
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    // 5552 is the largest run that cannot overflow before reducing
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    (b << 16) | a
+}
+
+/* Hash chains over the last WINDOW_SIZE positions, keyed by the three bytes
+ * starting at each position.
+ */
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8]) -> Matcher<'a> {
+        Matcher {
+            data,
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            previous: vec![usize::MAX; WINDOW_SIZE],
+        }
+    }
+
+    fn insert(&mut self, i: usize) {
+        if i + MIN_MATCH <= self.data.len() {
+            let h = hash(self.data, i);
+            self.previous[i % WINDOW_SIZE] = self.head[h];
+            self.head[h] = i;
+        }
+    }
+
+    /* Longest earlier match for the bytes at `i` as (length, distance). */
+    fn find(&self, i: usize) -> (usize, usize) {
+        let data = self.data;
+        let mut best = (0, 0);
+        if i + MIN_MATCH > data.len() {
+            return best;
+        }
+
+        let limit = (data.len() - i).min(MAX_MATCH);
+        let mut candidate = self.head[hash(data, i)];
+        let mut chain = 0;
+
+        while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[i..i + limit])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, i - candidate);
+                if length == limit {
+                    break;
+                }
+            }
+
+            // Slots are reused as the window slides, so a link that does not
+            // point backwards belongs to a newer position and ends the chain.
+            let next = self.previous[candidate % WINDOW_SIZE];
+            if next == usize::MAX || next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain += 1;
+        }
+
+        best
+    }
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter::new(vec![0x78, 0x01]);
+
+    // a single final block using the fixed codes
+    w.write_bits(1, 1);
+    w.write_bits(1, 2);
+
+    let mut matcher = Matcher::new(data);
+    let mut i = 0;
+    while i < data.len() {
+        let (length, distance) = matcher.find(i);
+
+        if length >= MIN_MATCH {
+            write_match(&mut w, length, distance);
+            for k in i..i + length {
+                matcher.insert(k);
+            }
+            i += length;
+        } else {
+            write_literal(&mut w, data[i] as u32);
+            matcher.insert(i);
+            i += 1;
+        }
+    }
+
+    write_literal(&mut w, 256);
+
+    let mut out = w.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| invalid("truncated deflate stream"))?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /* Drops the bits left in the current byte. */
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/* Canonical Huffman code given by the number of codes of every length and
+ * the symbols ordered by code.
+ */
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for length in 1..16 {
+            code |= r.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid("invalid Huffman code in deflate stream"))
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(r: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let literals = r.bits(5)? as usize + 257;
+    let distances = r.bits(5)? as usize + 1;
+    let code_lengths = r.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err(invalid("too many codes in deflate block"));
+    }
+
+    let mut lengths = [0u8; 19];
+    for &i in ORDER.iter().take(code_lengths) {
+        lengths[i] = r.bits(3)? as u8;
+    }
+    let length_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let symbol = length_code.decode(r)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("repeated code length with no previous length"))?;
+                (previous, 3 + r.bits(2)?)
+            }
+            17 => (0, 3 + r.bits(3)?),
+            _ => (0, 11 + r.bits(7)?),
+        };
+
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literals + distances {
+        return Err(invalid("code lengths overrun in deflate block"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+fn inflate_block(
+    r: &mut BitReader,
+    out: &mut Vec<u8>,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = literal.decode(r)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let l = symbol - 257;
+        if l >= LENGTH_BASE.len() {
+            return Err(invalid("invalid length symbol in deflate stream"));
+        }
+        let length = LENGTH_BASE[l] as usize + r.bits(LENGTH_EXTRA[l] as u32)? as usize;
+
+        let d = distance.decode(r)? as usize;
+        if d >= DISTANCE_BASE.len() {
+            return Err(invalid("invalid distance symbol in deflate stream"));
+        }
+        let back = DISTANCE_BASE[d] as usize + r.bits(DISTANCE_EXTRA[d] as u32)? as usize;
+        if back > out.len() {
+            return Err(invalid("distance too far back in deflate stream"));
+        }
+
+        // Copies may overlap their own output, so go byte by byte.
+        let start = out.len() - back;
+        for k in 0..length {
+            let byte = out[start + k];
+            out.push(byte);
+        }
+    }
+}
+
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    if data.len() < 6 {
+        return Err(invalid("truncated zlib stream"));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+        return Err(invalid("unsupported zlib header"));
+    }
+
+    let mut r = BitReader::new(&data[2..]);
+    let mut out = Vec::new();
+
+    loop {
+        let last = r.bits(1)? == 1;
+        match r.bits(2)? {
+            0 => {
+                r.align();
+                let header = r
+                    .data
+                    .get(r.position..r.position + 4)
+                    .ok_or_else(|| invalid("truncated deflate stream"))?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let complement = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if length != !complement & 0xffff {
+                    return Err(invalid("corrupt stored deflate block"));
+                }
+
+                let start = r.position + 4;
+                let stored = r
+                    .data
+                    .get(start..start + length)
+                    .ok_or_else(|| invalid("truncated deflate stream"))?;
+                out.extend_from_slice(stored);
+                r.position = start + length;
+            }
+            1 => {
+                let (literal, distance) = fixed_codes();
+                inflate_block(&mut r, &mut out, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_codes(&mut r)?;
+                inflate_block(&mut r, &mut out, &literal, &distance)?;
+            }
+            _ => return Err(invalid("invalid deflate block type")),
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    r.align();
+    let checksum = r
+        .data
+        .get(r.position..r.position + 4)
+        .ok_or_else(|| invalid("missing zlib checksum"))?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(invalid("zlib checksum mismatch"));
+    }
+
+    Ok(out)
+}